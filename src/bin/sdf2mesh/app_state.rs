@@ -0,0 +1,108 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+// The `ShaderType` derive generates `const fn check` items that are never called
+#![allow(dead_code)]
+
+use encase::ShaderType;
+use sdf2mesh::*;
+
+#[derive(Debug, ShaderType, Clone, Copy)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+#[derive(Debug, ShaderType, Clone, Copy)]
+pub struct Dims {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    pub z_slice_idx: u32,
+}
+
+#[derive(Debug, ShaderType)]
+pub struct AppState {
+    pub bb_min: Vec4,
+    pub bb_max: Vec4,
+    pub dims: Dims,
+    /// Number of vertices generated by previous slices
+    pub vertex_offset: u32,
+    /// Iso value of the surface
+    pub iso: f32,
+    /// Step of the numerical normal estimation
+    pub normal_eps: f32,
+}
+
+impl AppState {
+    // Translating Rust structures to WGSL is always tricky and can prove
+    // incredibly difficult to remember all the rules by which WGSL
+    // lays out and formats structs in memory. It is also often extremely
+    // frustrating to debug when things don't go right.
+    //
+    // You may sometimes see structs translated to bytes through
+    // using `#[repr(C)]` on the struct so that the struct has a defined,
+    // guaranteed internal layout and then implementing bytemuck's POD
+    // trait so that one can preform a bitwise cast. There are issues with
+    // this approach though as C's struct layouts aren't always compatible
+    // with WGSL, such as when special WGSL types like vec's and mat's
+    // get involved that have special alignment rules and especially
+    // when the target buffer is going to be used in the uniform memory
+    // space.
+    //
+    // Here though, we use the encase crate which makes translating potentially
+    // complex Rust structs easy through combined use of the [`ShaderType`] trait
+    // / derive macro and the buffer structs which hold data formatted for WGSL
+    // in either the storage or uniform spaces.
+    pub fn as_wgsl_bytes(&self) -> encase::internal::Result<Vec<u8>> {
+        let mut buffer = encase::UniformBuffer::new(Vec::new());
+        buffer.write(self)?;
+        Ok(buffer.into_inner())
+    }
+
+    pub fn set_z(&mut self, z_slice_idx: u32) {
+        self.dims.z_slice_idx = z_slice_idx;
+    }
+
+    pub fn set_vertex_offset(&mut self, vertex_offset: u32) {
+        self.vertex_offset = vertex_offset;
+    }
+
+    pub fn set_iso(&mut self, iso: f32) {
+        self.iso = iso;
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        let bounds = Bounds3D::centered(&Vec3D::new(2.0, 2.0, 2.0));
+
+        let min = bounds.min();
+        let max = bounds.max();
+        AppState {
+            bb_min: Vec4 {
+                x: min.x,
+                y: min.y,
+                z: min.z,
+                /* unused */ w: 0.0,
+            },
+            bb_max: Vec4 {
+                x: max.x,
+                y: max.y,
+                z: max.z,
+                /* unused */ w: 0.0,
+            },
+            dims: Dims {
+                x: 128,
+                y: 128,
+                z: 128,
+                z_slice_idx: 0,
+            },
+            vertex_offset: 0,
+            iso: 0.0,
+            normal_eps: 0.0001,
+        }
+    }
+}
@@ -28,15 +28,15 @@ fn cell_bounds(bounds: Bounds3D, res: vec3<u32>, pos: vec3<i32>) -> Bounds3D {
 
 fn cell_new(b: Bounds3D, pos: vec3i) -> Cell {
     return Cell(
-        array(  
-            sdf3d(vec3(b.min.x, b.min.y, b.min.z)),
-            sdf3d(vec3(b.max.x, b.min.y, b.min.z)),
-            sdf3d(vec3(b.min.x, b.max.y, b.min.z)),
-            sdf3d(vec3(b.max.x, b.max.y, b.min.z)),
-            sdf3d(vec3(b.min.x, b.min.y, b.max.z)),
-            sdf3d(vec3(b.max.x, b.min.y, b.max.z)),
-            sdf3d(vec3(b.min.x, b.max.y, b.max.z)),
-            sdf3d(vec3(b.max.x, b.max.y, b.max.z)),            
+        array(
+            sample_get(pos + vec3(0, 0, 0)),
+            sample_get(pos + vec3(1, 0, 0)),
+            sample_get(pos + vec3(0, 1, 0)),
+            sample_get(pos + vec3(1, 1, 0)),
+            sample_get(pos + vec3(0, 0, 1)),
+            sample_get(pos + vec3(1, 0, 1)),
+            sample_get(pos + vec3(0, 1, 1)),
+            sample_get(pos + vec3(1, 1, 1)),
         ),
         b, pos
     );
@@ -134,6 +134,9 @@ struct AppState {
     bb_min: vec4<f32>,
     bb_max: vec4<f32>,
     dims: vec4<u32>,
+    vertex_offset: u32,
+    iso: f32,
+    normal_eps: f32,
 }
 
 @group(0)
@@ -148,6 +151,43 @@ var tex_vertex_normals: texture_storage_2d<rgba32float, write>;
 @binding(2)
 var tex_vertex_positions: texture_storage_2d<rgba32float, write>;
 
+/// SDF samples of two consecutive z-planes of the lattice.
+/// Plane `z` is stored in slab `z % 2`.
+@group(0)
+@binding(3)
+var<storage, read_write> sdf_samples: array<f32>;
+
+/// Per cell of the current slice:
+/// x: Sign bits and `CELL_HAS_VERTEX`, y: Rank of vertex in row, z: Rank of first quad in row
+@group(0)
+@binding(4)
+var<storage, read_write> cells: array<vec4<u32>>;
+
+/// Per row of the current slice:
+/// x: Vertex count, y: Quad count, z: Vertex offset, w: Quad offset.
+/// The element after the last row holds the totals of the slice.
+@group(0)
+@binding(5)
+var<storage, read_write> rows: array<vec4<u32>>;
+
+/// Vertex indices of the cells of two consecutive slices.
+/// Slice `z` is stored in slab `z % 2`.
+@group(0)
+@binding(6)
+var<storage, read_write> vertex_index_map: array<u32>;
+
+// Binding 7 of group 0 is the uniform struct `params`, generated for parameters declared with `@param(uniform)`
+
+/// Quad indices of the current slice
+@group(1)
+@binding(0)
+var<storage, read_write> quad_indices: array<u32>;
+
+// Group 2 holds the textures and samplers of the channels `iChannel0` to `iChannel3` of ShaderToy shaders
+
+const CELL_HAS_VERTEX = 16u;
+const INVALID_INDEX = 0xffffffffu;
+
 
 fn grid_resolution() -> vec3<u32> {
     return vec3(app_state.dims.xyz);
@@ -158,24 +198,212 @@ fn state_bounds() -> Bounds3D {
     return Bounds3D(app_state.bb_min.xyz, app_state.bb_max.xyz);   
 }
 
+/// Index of lattice point `pos` in `sdf_samples`
+fn sample_index(pos: vec3i) -> u32 {
+    let res = grid_resolution();
+    let stride = res.x + 1u;
+    let slab = u32(pos.z) % 2u;
+    return (slab * (res.y + 1u) + u32(pos.y)) * stride + u32(pos.x);
+}
+
+fn sample_get(pos: vec3i) -> f32 {
+    return sdf_samples[sample_index(pos)];
+}
+
+/// Index of cell `pos` of the current slice in `cells`
+fn cell_index(pos: vec2u) -> u32 {
+    return pos.y * grid_resolution().x + pos.x;
+}
+
+/// Vertex index of cell `pos`, `INVALID_INDEX` if the cell has no vertex
+fn vertex_index_get(pos: vec3u) -> u32 {
+    let res = grid_resolution();
+    return vertex_index_map[((pos.z % 2u) * res.y + pos.y) * res.x + pos.x];
+}
+
+/// Number of quads generated by a cell in slice `z`
+fn cell_quad_count(flags: u32, pos: vec3u) -> u32 {
+    if (flags & CELL_HAS_VERTEX) == 0u {
+        return 0u;
+    }
+
+    let s3 = (flags & 8u) != 0u;
+    var count = 0u;
+    if ((flags & 1u) != 0u) != s3 && pos.y > 0u && pos.z > 0u { count += 1u; }
+    if ((flags & 2u) != 0u) != s3 && pos.x > 0u && pos.z > 0u { count += 1u; }
+    if ((flags & 4u) != 0u) != s3 && pos.x > 0u && pos.y > 0u { count += 1u; }
+    return count;
+}
+
+/// Samples the SDF once per lattice point of a z-plane.
+/// Plane `z_slice_idx + 1` is sampled for each slice, plane `z_slice_idx` only for the first slice.
+/// The iso value is subtracted, so the surface is always at the zero level set of the samples.
 @compute
-@workgroup_size(1)
+@workgroup_size(8, 8, 1)
+fn sample_main(@builtin(global_invocation_id) id: vec3u) {
+    let res = grid_resolution();
+    if id.x > res.x || id.y > res.y {
+        return;
+    }
+
+    let pos = vec3(i32(id.x), i32(id.y), i32(app_state.dims.w + 1u - id.z));
+    sdf_samples[sample_index(pos)] = sdf3d(cell_bounds(state_bounds(), res, pos).min) - app_state.iso;
+}
+
+@compute
+@workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) id: vec3u) {
+    let res = grid_resolution();
+    if id.x >= res.x || id.y >= res.y {
+        return;
+    }
+
     let pos = vec3(i32(id.x), i32(id.y), i32(app_state.dims.w));
-    let bounds = cell_bounds(state_bounds(), grid_resolution(), pos);
+    let bounds = cell_bounds(state_bounds(), res, pos);
     let cell = cell_new(bounds, pos);
 
     let p = cell_fetch_interpolated_pos(cell);
     if p.w >= 0.0 { // We have a vertex
-        let eps = app_state.bb_max.w;
-        let n = normalize(sdf3d_normal(p.xyz, eps));
+        // `sdf3d_vertex_normal` is generated for the normal estimation selected with `--normals`
+        let n = normalize(sdf3d_vertex_normal(p.xyz, app_state.normal_eps));
         let signs = cell_sign_changes_f32(cell);
 
         textureStore(tex_vertex_normals, pos.xy, vec4(n.xyz, signs));
         textureStore(tex_vertex_positions, pos.xy, p);
+        cells[cell_index(id.xy)] = vec4(u32(signs) | CELL_HAS_VERTEX, 0u, 0u, 0u);
     } else {
         textureStore(tex_vertex_normals, pos.xy, vec4(0.0, 0.0, 0.0, 0.0));
         textureStore(tex_vertex_positions, pos.xy, vec4(0.0, 0.0, 0.0, 0.0));
+        cells[cell_index(id.xy)] = vec4(0u);
+    }
+}
+
+/// Ranks the vertices and quads of each row.
+@compute
+@workgroup_size(64)
+fn scan_rows(@builtin(global_invocation_id) id: vec3u) {
+    let res = grid_resolution();
+    let y = id.x;
+    if y >= res.y {
+        return;
+    }
+
+    var vertex_count = 0u;
+    var quad_count = 0u;
+    for (var x = 0u; x < res.x; x++) {
+        let idx = cell_index(vec2(x, y));
+        let flags = cells[idx].x;
+        cells[idx].y = vertex_count;
+        cells[idx].z = quad_count;
+
+        if (flags & CELL_HAS_VERTEX) != 0u {
+            vertex_count += 1u;
+        }
+        quad_count += cell_quad_count(flags, vec3(x, y, app_state.dims.w));
+    }
+
+    rows[y] = vec4(vertex_count, quad_count, 0u, 0u);
+}
+
+/// Computes the vertex and quad offsets of each row.
+@compute
+@workgroup_size(1)
+fn scan_slice() {
+    let res = grid_resolution();
+    var vertex_offset = 0u;
+    var quad_offset = 0u;
+    for (var y = 0u; y < res.y; y++) {
+        rows[y].z = vertex_offset;
+        rows[y].w = quad_offset;
+        vertex_offset += rows[y].x;
+        quad_offset += rows[y].y;
+    }
+
+    rows[res.y] = vec4(vertex_offset, quad_offset, 0u, 0u);
+}
+
+/// Assigns the vertex indices of the current slice.
+/// Vertices are numbered row by row, like they are read back from the vertex textures.
+@compute
+@workgroup_size(8, 8, 1)
+fn index_main(@builtin(global_invocation_id) id: vec3u) {
+    let res = grid_resolution();
+    if id.x >= res.x || id.y >= res.y {
+        return;
+    }
+
+    let cell = cells[cell_index(id.xy)];
+    var index = INVALID_INDEX;
+    if (cell.x & CELL_HAS_VERTEX) != 0u {
+        index = app_state.vertex_offset + rows[id.y].z + cell.y;
     }
+
+    vertex_index_map[((app_state.dims.w % 2u) * res.y + id.y) * res.x + id.x] = index;
 }
 
+/// Writes the quad `(a, b, c, d)`, reversed if `swap` is true
+fn emit_quad(quad_idx: u32, a: u32, b: u32, c: u32, d: u32, swap: bool) {
+    var q = vec4(a, b, c, d);
+    if swap {
+        q = q.wzyx;
+    }
+
+    let i = quad_idx * 4u;
+    quad_indices[i + 0u] = q.x;
+    quad_indices[i + 1u] = q.y;
+    quad_indices[i + 2u] = q.z;
+    quad_indices[i + 3u] = q.w;
+}
+
+/// Generates the quads of the current slice.
+@compute
+@workgroup_size(8, 8, 1)
+fn quad_main(@builtin(global_invocation_id) id: vec3u) {
+    let res = grid_resolution();
+    if id.x >= res.x || id.y >= res.y {
+        return;
+    }
+
+    let cell = cells[cell_index(id.xy)];
+    if (cell.x & CELL_HAS_VERTEX) == 0u {
+        return;
+    }
+
+    let x = id.x;
+    let y = id.y;
+    let z = app_state.dims.w;
+    let s0 = (cell.x & 1u) != 0u;
+    let s1 = (cell.x & 2u) != 0u;
+    let s2 = (cell.x & 4u) != 0u;
+    let s3 = (cell.x & 8u) != 0u;
+    var quad_idx = rows[y].w + cell.z;
+
+    if s0 != s3 && y > 0u && z > 0u {
+        emit_quad(quad_idx,
+            vertex_index_get(vec3(x, y - 1u, z - 1u)),
+            vertex_index_get(vec3(x, y, z - 1u)),
+            vertex_index_get(vec3(x, y, z)),
+            vertex_index_get(vec3(x, y - 1u, z)),
+            s0);
+        quad_idx += 1u;
+    }
+
+    if s1 != s3 && x > 0u && z > 0u {
+        emit_quad(quad_idx,
+            vertex_index_get(vec3(x - 1u, y, z - 1u)),
+            vertex_index_get(vec3(x, y, z - 1u)),
+            vertex_index_get(vec3(x, y, z)),
+            vertex_index_get(vec3(x - 1u, y, z)),
+            !s1);
+        quad_idx += 1u;
+    }
+
+    if s2 != s3 && x > 0u && y > 0u {
+        emit_quad(quad_idx,
+            vertex_index_get(vec3(x - 1u, y - 1u, z)),
+            vertex_index_get(vec3(x, y - 1u, z)),
+            vertex_index_get(vec3(x, y, z)),
+            vertex_index_get(vec3(x - 1u, y, z)),
+            s2);
+    }
+}
@@ -3,91 +3,76 @@
 
 extern crate sdf2mesh;
 
-use encase::ShaderType;
+mod app_state;
+
+use app_state::*;
 use sdf2mesh::{png::ToPngFile, *};
 
 use clap::Parser;
+use encase::ShaderType;
 use shader::Sdf3DShader;
 
-#[derive(Debug, ShaderType, Clone, Copy)]
-struct Vec4 {
-    x: f32,
-    y: f32,
-    z: f32,
-    w: f32,
+/// Workgroup size of the compute shaders in X and Y direction, see `dualcontour.wgsl`
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Workgroup size of the row scan compute shader, see `dualcontour.wgsl`
+const ROW_WORKGROUP_SIZE: u32 = 64;
+
+/// Where the triangles of the mesh are assembled
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum MeshAssembly {
+    /// Triangle indices are generated by a compute shader
+    Gpu,
+    /// Triangles are assembled from the vertex list on the CPU
+    Cpu,
+    /// Triangles are assembled from the vertex list on the CPU using multiple threads
+    CpuParallel,
 }
 
-#[derive(Debug, ShaderType, Clone, Copy)]
-struct Dims {
-    x: u32,
-    y: u32,
-    z: u32,
-    z_slice_idx: u32,
+/// How the quads are split into triangles, see `mesh::Triangulation`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Triangulation {
+    /// Always split along the same diagonal
+    Fixed,
+    /// Split along the shorter diagonal
+    ShortestDiagonal,
+    /// Split along the diagonal whose triangles agree best with the vertex normals
+    Normals,
+    /// Keep quads untriangulated, for output formats that support them
+    Quads,
 }
 
-#[derive(Debug, ShaderType)]
-struct AppState {
-    pub bb_min: Vec4,
-    pub bb_max: Vec4,
-    pub dims: Dims,
-}
-
-impl AppState {
-    // Translating Rust structures to WGSL is always tricky and can prove
-    // incredibly difficult to remember all the rules by which WGSL
-    // lays out and formats structs in memory. It is also often extremely
-    // frustrating to debug when things don't go right.
-    //
-    // You may sometimes see structs translated to bytes through
-    // using `#[repr(C)]` on the struct so that the struct has a defined,
-    // guaranteed internal layout and then implementing bytemuck's POD
-    // trait so that one can preform a bitwise cast. There are issues with
-    // this approach though as C's struct layouts aren't always compatible
-    // with WGSL, such as when special WGSL types like vec's and mat's
-    // get involved that have special alignment rules and especially
-    // when the target buffer is going to be used in the uniform memory
-    // space.
-    //
-    // Here though, we use the encase crate which makes translating potentially
-    // complex Rust structs easy through combined use of the [`ShaderType`] trait
-    // / derive macro and the buffer structs which hold data formatted for WGSL
-    // in either the storage or uniform spaces.
-    fn as_wgsl_bytes(&self) -> encase::internal::Result<Vec<u8>> {
-        let mut buffer = encase::UniformBuffer::new(Vec::new());
-        buffer.write(self)?;
-        Ok(buffer.into_inner())
-    }
-
-    fn set_z(&mut self, z_slice_idx: u32) {
-        self.dims.z_slice_idx = z_slice_idx;
+impl From<Triangulation> for mesh::Triangulation {
+    fn from(triangulation: Triangulation) -> Self {
+        match triangulation {
+            Triangulation::Fixed => Self::Fixed,
+            Triangulation::ShortestDiagonal => Self::ShortestDiagonal,
+            Triangulation::Normals => Self::Normals,
+            Triangulation::Quads => Self::Quads,
+        }
     }
 }
 
-impl Default for AppState {
-    fn default() -> Self {
-        let bounds = Bounds3D::centered(&Vec3D::new(2.0, 2.0, 2.0));
+/// How the normals of the mesh vertices are estimated, see `shader::NormalEstimation`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum NormalEstimation {
+    /// Analytic normals if the shader defines `sdf3d_gradient`, tetrahedral otherwise
+    Auto,
+    /// Gradient from 4 samples at the corners of a tetrahedron
+    Tetrahedral,
+    /// Gradient from 6 samples, central differences along each axis
+    Central,
+    /// Gradient from the function `fn sdf3d_gradient(p: vec3f) -> vec3f` defined in the shader
+    Analytic,
+}
 
-        let min = bounds.min();
-        let max = bounds.max();
-        AppState {
-            bb_min: Vec4 {
-                x: min.x,
-                y: min.y,
-                z: min.z,
-                /* unused */ w: 0.0,
-            },
-            bb_max: Vec4 {
-                x: max.x,
-                y: max.y,
-                z: max.z,
-                /* eps */ w: 0.0001,
-            },
-            dims: Dims {
-                x: 128,
-                y: 128,
-                z: 128,
-                z_slice_idx: 0,
-            },
+impl From<NormalEstimation> for shader::NormalEstimation {
+    fn from(normals: NormalEstimation) -> Self {
+        match normals {
+            NormalEstimation::Auto => Self::Auto,
+            NormalEstimation::Tetrahedral => Self::Tetrahedral,
+            NormalEstimation::Central => Self::Central,
+            NormalEstimation::Analytic => Self::Analytic,
         }
     }
 }
@@ -99,25 +84,90 @@ struct Arguments {
     #[arg(short = 'i', long)]
     sdf: Option<String>,
 
+    /// Additional directory to search for files included by the SDF file
+    #[arg(short = 'I', long)]
+    include_path: Vec<String>,
+
+    /// Additional directory to search for module files imported with `use`.
+    /// Directories in the environment variable SDF2MESH_MODULE_PATH are searched afterwards
+    #[arg(long)]
+    module_path: Vec<String>,
+
     /// Input ShaderToy shader ID
     #[arg(long)]
     shadertoy_id: Option<String>,
 
-    /// ShaderToy SDF name
-    #[arg(long, default_value = "sdf")]
+    /// Input ShaderToy shader exported as JSON, e.g. a response of the ShaderToy API
+    #[arg(long)]
+    shadertoy_json: Option<String>,
+
+    /// Directory of the cache of shaders fetched from ShaderToy.
+    /// Defaults to SDF2MESH_SHADERTOY_CACHE or the user's cache directory
+    #[arg(long)]
+    shadertoy_cache: Option<String>,
+
+    /// Do not read or store fetched ShaderToy shaders in the cache
+    #[arg(long)]
+    no_shadertoy_cache: bool,
+
+    /// Key for the ShaderToy API. Defaults to SDF2MESH_SHADERTOY_API_KEY or the key of sdf2mesh
+    #[arg(long)]
+    shadertoy_api_key: Option<String>,
+
+    /// Base URL of the ShaderToy API, e.g. of a mirror.
+    /// Defaults to SDF2MESH_SHADERTOY_API_URL or https://www.shadertoy.com/api/v1
+    #[arg(long)]
+    shadertoy_api_url: Option<String>,
+
+    /// Number of times a failed request to the ShaderToy API is retried
+    #[arg(long, default_value_t = 3)]
+    shadertoy_retries: u32,
+
+    /// Name of the ShaderToy render pass that defines the SDF, e.g. "Buffer A". Default: the image pass.
+    /// The code of the common pass is always included
+    #[arg(long)]
+    shadertoy_pass: Option<String>,
+
+    /// Image bound to `iChannel0` of a ShaderToy shader, a PNG image or a ShaderToy volume (.bin).
+    /// Replaces the texture of the input of the channel
+    #[arg(long)]
+    channel0: Option<std::path::PathBuf>,
+
+    /// Image bound to `iChannel1` of a ShaderToy shader, see `--channel0`
+    #[arg(long)]
+    channel1: Option<std::path::PathBuf>,
+
+    /// Image bound to `iChannel2` of a ShaderToy shader, see `--channel0`
+    #[arg(long)]
+    channel2: Option<std::path::PathBuf>,
+
+    /// Image bound to `iChannel3` of a ShaderToy shader, see `--channel0`
+    #[arg(long)]
+    channel3: Option<std::path::PathBuf>,
+
+    /// ShaderToy SDF name, detected from the functions with the signature `float(vec3)` if not given
+    #[arg(long)]
     shadertoy_sdf: Option<String>,
 
     /// Input GLSL fragment shader
     #[arg(long)]
     glsl: Option<String>,
 
-    /// GLSL SDF name
-    #[arg(long, default_value = "sdf")]
+    /// GLSL SDF name, detected from the functions with the signature `float(vec3)` if not given
+    #[arg(long)]
     glsl_sdf: Option<String>,
 
     /// Output mesh file (supports STL and PLY output)
-    #[arg(short = '0', long)]
-    mesh: String,
+    #[arg(short = '0', long, required_unless_present = "list_params")]
+    mesh: Option<String>,
+
+    /// Override a parameter declared with `@param` or a text declared with `@text` in the SDF file, e.g. `KEY_SIZE=18` or `LABEL="HELLO"`
+    #[arg(short = 'D', long, value_parser = parse_define)]
+    define: Vec<(String, String)>,
+
+    /// List the parameters and texts declared in the SDF file and exit
+    #[arg(long)]
+    list_params: bool,
 
     /// Output WGSL file for debugging
     #[arg(long)]
@@ -134,6 +184,69 @@ struct Arguments {
     /// Size of bounding box. Default: 2
     #[arg(short = 'b', long)]
     bounds: Option<f32>,
+
+    /// Iso values of the surfaces, e.g. `--iso 0.02` or `--iso -0.1,0,0.1`. Default: 0
+    #[arg(
+        long,
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        default_value = "0"
+    )]
+    iso: Vec<f32>,
+
+    /// Write the surfaces of all iso values into a single mesh file
+    #[arg(long)]
+    combine_iso: bool,
+
+    /// Mesh assembly
+    #[arg(long, value_enum, default_value_t = MeshAssembly::Gpu)]
+    assembly: MeshAssembly,
+
+    /// How quads are split into triangles. `quads` is only supported by PLY output
+    #[arg(long, value_enum, default_value_t = Triangulation::Fixed)]
+    triangulation: Triangulation,
+
+    /// How the vertex normals are estimated
+    #[arg(long, value_enum, default_value_t = NormalEstimation::Auto)]
+    normals: NormalEstimation,
+
+    /// Step of the numerical normal estimation. Default: a tenth of the cell size
+    #[arg(long)]
+    normal_eps: Option<f32>,
+}
+
+/// Default step of the numerical normal estimation relative to the cell size
+const NORMAL_EPS_PER_CELL: f32 = 0.1;
+
+/// Storage buffers of the compute shader: SDF samples, cells, rows, vertex index map and quad indices
+const STORAGE_BUFFERS_PER_SHADER_STAGE: u32 = 5;
+
+/// Downlevel limits, raised where the compute shader needs more
+fn required_limits(adapter: &wgpu::Adapter) -> Result<wgpu::Limits, String> {
+    let supported = adapter.limits();
+    if supported.max_storage_buffers_per_shader_stage < STORAGE_BUFFERS_PER_SHADER_STAGE {
+        return Err(format!(
+            "The GPU adapter supports {} storage buffers per shader stage, {STORAGE_BUFFERS_PER_SHADER_STAGE} are required (`max_storage_buffers_per_shader_stage`)",
+            supported.max_storage_buffers_per_shader_stage
+        ));
+    }
+    Ok(wgpu::Limits {
+        max_storage_buffers_per_shader_stage: STORAGE_BUFFERS_PER_SHADER_STAGE,
+        ..wgpu::Limits::downlevel_defaults()
+    })
+}
+
+fn storage_buffer_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
 }
 
 impl From<&Arguments> for AppState {
@@ -162,7 +275,7 @@ impl From<&Arguments> for AppState {
                 x: max.x,
                 y: max.y,
                 z: max.z,
-                /* eps */ w: 0.0001,
+                /* unused */ w: 0.0,
             },
             dims: Dims {
                 x: res,
@@ -170,10 +283,35 @@ impl From<&Arguments> for AppState {
                 z: res,
                 z_slice_idx: 0u32,
             },
+            vertex_offset: 0,
+            iso: 0.0,
+            normal_eps: args
+                .normal_eps
+                .unwrap_or(bounds.max_extent() / res as f32 * NORMAL_EPS_PER_CELL),
         }
     }
 }
 
+/// Parse a parameter override like `KEY_SIZE=18`
+fn parse_define(define: &str) -> Result<(String, String), String> {
+    define
+        .split_once('=')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .ok_or(format!("Expected NAME=VALUE, got `{define}`"))
+}
+
+/// Path of the mesh of an iso level, e.g. `mesh_iso1.stl` for `mesh.stl`
+fn level_mesh_path(path: &str, level_idx: usize) -> std::path::PathBuf {
+    let path = std::path::Path::new(path);
+    let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!("_iso{level_idx}"));
+    if let Some(ext) = path.extension() {
+        file_name.push(".");
+        file_name.push(ext);
+    }
+    path.with_file_name(file_name)
+}
+
 async fn run(args: Arguments) {
     let mut state = AppState::from(&args);
 
@@ -182,12 +320,19 @@ async fn run(args: Arguments) {
         .request_adapter(&wgpu::RequestAdapterOptions::default())
         .await
         .unwrap();
+    let required_limits = match required_limits(&adapter) {
+        Ok(limits) => limits,
+        Err(err) => {
+            log::error!("{err}");
+            return;
+        }
+    };
     let (device, queue) = adapter
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
                 required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::downlevel_defaults(),
+                required_limits,
                 memory_hints: wgpu::MemoryHints::default(),
             },
             None,
@@ -201,38 +346,138 @@ async fn run(args: Arguments) {
         log::info!("Reading SDF from ShaderToy (shader ID {})", {
             shadertoy_id
         });
-        sdf3d_file = shader::Sdf3DShader::from_shadertoy_api(
+        let client = match shadertoy_client(&args) {
+            Ok(client) => client,
+            Err(err) => {
+                log::error!("Could not create ShaderToy client: {err}");
+                return;
+            }
+        };
+        sdf3d_file = match shader::Sdf3DShader::from_shadertoy_api(
+            &client,
             shadertoy_id,
-            args.shadertoy_sdf.unwrap_or("sdf".into()).as_str(),
+            &shadertoy_options(&args),
         )
         .await
-        .unwrap();
+        {
+            Ok(sdf3d_file) => sdf3d_file,
+            Err(err) => {
+                log::error!(
+                    "Could not read ShaderToy shader {shadertoy_id}: {err}{}",
+                    error_hint(&err, "--shadertoy-sdf")
+                );
+                return;
+            }
+        };
+    } else if let Some(json) = &args.shadertoy_json {
+        log::info!("Reading SDF from ShaderToy shader {json}...");
+        let client = match shadertoy_client(&args) {
+            Ok(client) => client,
+            Err(err) => {
+                log::error!("Could not create ShaderToy client: {err}");
+                return;
+            }
+        };
+        sdf3d_file = match shader::Sdf3DShader::from_shadertoy_json(
+            &client,
+            json,
+            &shadertoy_options(&args),
+        )
+        .await
+        {
+            Ok(sdf3d_file) => sdf3d_file,
+            Err(err) => {
+                log::error!(
+                    "Could not read ShaderToy shader {json}: {err}{}",
+                    error_hint(&err, "--shadertoy-sdf")
+                );
+                return;
+            }
+        };
     } else if let Some(sdf) = &args.sdf {
         log::info!("Reading SDF from {}...", sdf);
 
-        sdf3d_file = shader::Sdf3DShader::from_path(sdf);
+        sdf3d_file = Sdf3DShader::new();
+        for include_path in &args.include_path {
+            sdf3d_file.add_include_path(include_path);
+        }
+        for module_path in &args.module_path {
+            sdf3d_file.add_module_path(module_path);
+        }
+        sdf3d_file.add_module_paths_from_env();
+        for (name, value) in &args.define {
+            sdf3d_file.define(name, value);
+        }
+        if let Err(err) = sdf3d_file.load_file(sdf) {
+            log::error!("{err}");
+            return;
+        }
     } else if let Some(glsl) = &args.glsl {
         log::info!("Reading SDF from GLSL fragment shader {}...", glsl);
-        sdf3d_file = shader::Sdf3DShader::from_glsl_fragment_shader(
-            glsl,
-            args.glsl_sdf.unwrap_or("sdf".into()).as_str(),
-        )
-        .unwrap();
+        sdf3d_file =
+            match shader::Sdf3DShader::from_glsl_fragment_shader(glsl, args.glsl_sdf.as_deref()) {
+                Ok(sdf3d_file) => sdf3d_file,
+                Err(err) => {
+                    log::error!(
+                        "Could not read GLSL fragment shader {glsl}: {err}{}",
+                        error_hint(&err, "--glsl-sdf")
+                    );
+                    return;
+                }
+            };
+    }
+
+    if args.list_params {
+        for param in sdf3d_file.params() {
+            println!("{param}");
+        }
+        for text in sdf3d_file.texts() {
+            println!("{text}");
+        }
+        return;
+    }
+
+    match sdf3d_file.add_normal_estimation(args.normals.into()) {
+        Ok(normals) => log::info!("Normal estimation: {normals:?}, eps {}", state.normal_eps),
+        Err(err) => {
+            log::error!("{err}");
+            return;
+        }
     }
 
     if let Some(debug_wgsl) = &args.debug_wgsl {
         sdf3d_file.write_to_file(debug_wgsl).unwrap();
     }
 
-    sdf3d_file.add_to_source(include_str!("dualcontour.wgsl"));
+    sdf3d_file.add_to_source("dualcontour.wgsl", include_str!("dualcontour.wgsl"));
 
-    let shader = sdf3d_file.create_shader_module(&device);
+    let shader = match sdf3d_file.create_shader_module(&device) {
+        Ok(shader) => shader,
+        Err(err) => {
+            log::error!("{err}");
+            return;
+        }
+    };
 
     let mut normal_texture =
         texture::Rgba32FloatTextureStorage::new(&device, (state.dims.x, state.dims.y), 1);
     let mut position_texture =
         texture::Rgba32FloatTextureStorage::new(&device, (state.dims.x, state.dims.y), 2);
 
+    let mut rows_buffer =
+        buffer::StorageBuffer::<[u32; 4]>::new(&device, state.dims.y as usize + 1, 5);
+    // Initial capacity for a few quads per row and column, the buffer grows if needed
+    let mut quad_buffer = buffer::StorageBuffer::<u32>::new(
+        &device,
+        4 * 4 * (state.dims.x + state.dims.y) as usize,
+        0,
+    );
+
+    // Parameters declared with `@param(uniform)`
+    let params_buffer =
+        buffer::ParamsBuffer::new(&device, sdf3d_file.params(), shader::PARAMS_BINDING);
+    params_buffer.write(&queue);
+
     let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: None,
         entries: &[
@@ -248,16 +493,85 @@ async fn run(args: Arguments) {
             },
             normal_texture.bind_group_layout_entry(),
             position_texture.bind_group_layout_entry(),
+            storage_buffer_layout_entry(3),
+            storage_buffer_layout_entry(4),
+            rows_buffer.bind_group_layout_entry(),
+            storage_buffer_layout_entry(6),
+            params_buffer.bind_group_layout_entry(),
         ],
     });
+    let quad_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[quad_buffer.bind_group_layout_entry()],
+        });
+
+    // Textures of the channels of ShaderToy shaders
+    let channel_textures: Vec<_> = sdf3d_file
+        .channels()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, channel)| {
+            let channel = channel.as_ref()?;
+            Some(texture::ChannelTexture::new(
+                &device, &queue, channel, index,
+            ))
+        })
+        .collect();
+    let channel_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &channel_textures
+                .iter()
+                .flat_map(|texture| texture.bind_group_layout_entries())
+                .collect::<Vec<_>>(),
+        });
+    let channel_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &channel_bind_group_layout,
+        entries: &channel_textures
+            .iter()
+            .flat_map(|texture| texture.bind_group_entries())
+            .collect::<Vec<_>>(),
+    });
 
     let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
         label: None,
-        size: std::mem::size_of::<AppState>() as u64,
+        // Structs in the uniform address space are padded to 16 bytes
+        size: AppState::min_size().get().next_multiple_of(16),
         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         mapped_at_creation: false,
     });
 
+    let cell_count = state.dims.x as u64 * state.dims.y as u64;
+
+    // SDF samples of two z-planes with (x + 1) * (y + 1) lattice points each
+    let sample_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 2
+            * (state.dims.x as u64 + 1)
+            * (state.dims.y as u64 + 1)
+            * std::mem::size_of::<f32>() as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+
+    // Flags and ranks of the cells of a slice
+    let cell_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: cell_count * std::mem::size_of::<[u32; 4]>() as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+
+    // Vertex indices of the cells of two slices
+    let vertex_index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 2 * cell_count * std::mem::size_of::<u32>() as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+
     let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: None,
         layout: &bind_group_layout,
@@ -272,95 +586,306 @@ async fn run(args: Arguments) {
             },
             normal_texture.bind_group_entry(),
             position_texture.bind_group_entry(),
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: sample_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: cell_buffer.as_entire_binding(),
+            },
+            rows_buffer.bind_group_entry(),
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: vertex_index_buffer.as_entire_binding(),
+            },
+            params_buffer.bind_group_entry(),
         ],
     });
 
+    // The quad buffer grows with the number of quads, so it has its own bind group
+    let create_quad_bind_group = |quad_buffer: &buffer::StorageBuffer<u32>| {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &quad_bind_group_layout,
+            entries: &[quad_buffer.bind_group_entry()],
+        })
+    };
+    let mut quad_bind_group = create_quad_bind_group(&quad_buffer);
+
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: None,
-        bind_group_layouts: &[&bind_group_layout],
+        bind_group_layouts: &[
+            &bind_group_layout,
+            &quad_bind_group_layout,
+            &channel_bind_group_layout,
+        ],
         push_constant_ranges: &[],
     });
-    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: None,
-        layout: Some(&pipeline_layout),
-        module: &shader,
-        entry_point: Some("main"),
-        compilation_options: Default::default(),
-        cache: None,
-    });
+    let create_pipeline = |entry_point: &str| {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            cache: None,
+        })
+    };
+    let sample_pipeline = create_pipeline("sample_main");
+    let pipeline = create_pipeline("main");
+    let scan_rows_pipeline = create_pipeline("scan_rows");
+    let scan_slice_pipeline = create_pipeline("scan_slice");
+    let index_pipeline = create_pipeline("index_main");
+    let quad_pipeline = create_pipeline("quad_main");
 
     log::info!("Wgpu context set up.");
 
-    let mut vertex_items =
-        mesh::VertexList::with_capacity(state.dims.x as usize * state.dims.y as usize);
-
-    //----------------------------------------
-    for z_slice_idx in 0..state.dims.z {
-        state.set_z(z_slice_idx);
-        queue.write_buffer(
-            &uniform_buffer,
-            0,
-            &state
-                .as_wgsl_bytes()
-                .expect("Error in encase translating AppState struct to WGSL bytes."),
-        );
-
-        let mut command_encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        {
-            let mut compute_pass =
-                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                    label: None,
-                    timestamp_writes: None,
-                });
-            compute_pass.set_bind_group(0, &bind_group, &[]);
-            compute_pass.set_pipeline(&pipeline);
-            compute_pass.dispatch_workgroups(state.dims.x, state.dims.z, 1);
+    let gpu_assembly = args.assembly == MeshAssembly::Gpu;
+    let mut meshes = Vec::with_capacity(args.iso.len());
+
+    for &iso in &args.iso {
+        log::info!("Generating mesh for iso value {iso}...");
+        state.set_iso(iso);
+
+        let mut triangle_mesh = mesh::TriangleMesh::default();
+        let mut vertex_items = mesh::VertexList::default();
+        if !gpu_assembly {
+            vertex_items =
+                mesh::VertexList::with_capacity(state.dims.x as usize * state.dims.y as usize);
         }
 
-        normal_texture.copy_texture_to_buffer(&mut command_encoder);
-        position_texture.copy_texture_to_buffer(&mut command_encoder);
+        //----------------------------------------
+        for z_slice_idx in 0..state.dims.z {
+            state.set_z(z_slice_idx);
+            state.set_vertex_offset(triangle_mesh.num_vertices() as u32);
+            queue.write_buffer(
+                &uniform_buffer,
+                0,
+                &state
+                    .as_wgsl_bytes()
+                    .expect("Error in encase translating AppState struct to WGSL bytes."),
+            );
 
-        normal_texture.map_buffer(&device).await;
-        position_texture.map_buffer(&device).await;
+            let mut command_encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            {
+                let mut compute_pass =
+                    command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                        label: None,
+                        timestamp_writes: None,
+                    });
+                compute_pass.set_bind_group(0, &bind_group, &[]);
+                compute_pass.set_bind_group(1, &quad_bind_group, &[]);
+                compute_pass.set_bind_group(channel::CHANNEL_GROUP, &channel_bind_group, &[]);
+
+                // Sample the next z-plane, the first slice also needs its own plane
+                compute_pass.set_pipeline(&sample_pipeline);
+                compute_pass.dispatch_workgroups(
+                    (state.dims.x + 1).div_ceil(WORKGROUP_SIZE),
+                    (state.dims.y + 1).div_ceil(WORKGROUP_SIZE),
+                    if z_slice_idx == 0 { 2 } else { 1 },
+                );
+
+                compute_pass.set_pipeline(&pipeline);
+                compute_pass.dispatch_workgroups(
+                    state.dims.x.div_ceil(WORKGROUP_SIZE),
+                    state.dims.y.div_ceil(WORKGROUP_SIZE),
+                    1,
+                );
+
+                if gpu_assembly {
+                    // Number the vertices of the slice
+                    compute_pass.set_pipeline(&scan_rows_pipeline);
+                    compute_pass.dispatch_workgroups(
+                        state.dims.y.div_ceil(ROW_WORKGROUP_SIZE),
+                        1,
+                        1,
+                    );
+                    compute_pass.set_pipeline(&scan_slice_pipeline);
+                    compute_pass.dispatch_workgroups(1, 1, 1);
+                    compute_pass.set_pipeline(&index_pipeline);
+                    compute_pass.dispatch_workgroups(
+                        state.dims.x.div_ceil(WORKGROUP_SIZE),
+                        state.dims.y.div_ceil(WORKGROUP_SIZE),
+                        1,
+                    );
+                }
+            }
 
-        for y in 0..state.dims.y {
-            for x in 0..state.dims.x {
-                let p = position_texture.get_rgba(x, y);
+            normal_texture.copy_texture_to_buffer(&mut command_encoder);
+            position_texture.copy_texture_to_buffer(&mut command_encoder);
+
+            if gpu_assembly {
+                // The totals of the slice are stored after the last row
+                let totals = state.dims.y as usize;
+                rows_buffer.copy_buffer_to_staging(&mut command_encoder, totals..totals + 1);
+            }
 
-                if p.3 > 0.0 {
-                    let n = normal_texture.get_rgba(x, y);
-                    let vertex = Vertex {
-                        normal: Vec3D::new(n.0, n.1, n.2),
-                        pos: Vec3D::new(p.0, p.1, p.2),
-                    };
-                    let cell = (x as u16, y as u16, z_slice_idx as u16);
-                    let s = n.3 as u32;
-                    let sign_changes = (s & 1 != 0, s & 2 != 0, s & 4 != 0, s & 8 != 0);
+            queue.submit(Some(command_encoder.finish()));
 
-                    vertex_items.insert(cell, sign_changes, vertex);
+            normal_texture.map_buffer(&device).await;
+            position_texture.map_buffer(&device).await;
+
+            for y in 0..state.dims.y {
+                for x in 0..state.dims.x {
+                    let p = position_texture.get_rgba(x, y);
+
+                    if p.3 > 0.0 {
+                        let n = normal_texture.get_rgba(x, y);
+                        let vertex = Vertex {
+                            normal: Vec3D::new(n.0, n.1, n.2),
+                            pos: Vec3D::new(p.0, p.1, p.2),
+                        };
+
+                        if gpu_assembly {
+                            triangle_mesh.add_vertex(vertex);
+                        } else {
+                            let cell = (x as u16, y as u16, z_slice_idx as u16);
+                            let s = n.3 as u32;
+                            let sign_changes = (s & 1 != 0, s & 2 != 0, s & 4 != 0, s & 8 != 0);
+
+                            vertex_items.insert(cell, sign_changes, vertex);
+                        }
+                    }
+                }
+            }
+
+            if gpu_assembly {
+                rows_buffer.map_buffer(&device).await;
+                let quad_count = rows_buffer.data()[0][1] as usize;
+                if quad_buffer.reserve(&device, quad_count * 4) {
+                    quad_bind_group = create_quad_bind_group(&quad_buffer);
                 }
+
+                let mut command_encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                {
+                    let mut compute_pass =
+                        command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                            label: None,
+                            timestamp_writes: None,
+                        });
+                    compute_pass.set_bind_group(0, &bind_group, &[]);
+                    compute_pass.set_bind_group(1, &quad_bind_group, &[]);
+                    compute_pass.set_bind_group(channel::CHANNEL_GROUP, &channel_bind_group, &[]);
+                    compute_pass.set_pipeline(&quad_pipeline);
+                    compute_pass.dispatch_workgroups(
+                        state.dims.x.div_ceil(WORKGROUP_SIZE),
+                        state.dims.y.div_ceil(WORKGROUP_SIZE),
+                        1,
+                    );
+                }
+                quad_buffer.copy_buffer_to_staging(&mut command_encoder, 0..quad_count * 4);
+                queue.submit(Some(command_encoder.finish()));
+
+                quad_buffer.map_buffer(&device).await;
+                triangle_mesh.add_quad_indices(quad_buffer.data(), args.triangulation.into());
+            }
+
+            if let Some(path) = &args.debug_png {
+                normal_texture.to_png_file(format!("{path}{z_slice_idx:04}_normal.png"));
+                position_texture.to_png_file(format!("{path}{z_slice_idx:04}_position.png"));
+            }
+
+            if z_slice_idx % 128 == 0 {
+                log::info!("Slice #{}", z_slice_idx);
             }
         }
 
-        if let Some(path) = &args.debug_png {
-            normal_texture.to_png_file(format!("{path}{z_slice_idx:04}_normal.png"));
-            position_texture.to_png_file(format!("{path}{z_slice_idx:04}_position.png"));
+        match args.assembly {
+            MeshAssembly::Gpu => {}
+            MeshAssembly::Cpu => {
+                triangle_mesh =
+                    mesh::TriangleMesh::from_vertex_list(vertex_items, args.triangulation.into())
+            }
+            MeshAssembly::CpuParallel => {
+                triangle_mesh = mesh::TriangleMesh::from_vertex_list_parallel(
+                    vertex_items,
+                    args.triangulation.into(),
+                )
+            }
         }
+        log::info!("Mesh has {} vertices.", triangle_mesh.num_vertices());
+        meshes.push(triangle_mesh);
+    }
+
+    if meshes.len() > 1 && args.combine_iso {
+        let mut combined = mesh::TriangleMesh::default();
+        for mesh in meshes.drain(..) {
+            combined.append(mesh);
+        }
+        meshes.push(combined);
+    }
+
+    let mesh_path = args.mesh.as_deref().expect("--mesh is required");
+    for (level_idx, triangle_mesh) in meshes.iter().enumerate() {
+        let path = if meshes.len() > 1 {
+            level_mesh_path(mesh_path, level_idx)
+        } else {
+            std::path::PathBuf::from(mesh_path)
+        };
 
-        if z_slice_idx % 128 == 0 {
-            log::info!("Slice #{}", z_slice_idx);
+        if let Err(err) = triangle_mesh.write_to_file(&path) {
+            log::error!("Could not write mesh to {}!", err);
         }
 
-        queue.submit(Some(command_encoder.finish()));
+        log::info!("Mesh written to {}", path.display())
     }
-    log::info!("Mesh has {} vertices.", vertex_items.len());
+}
+
+/// Hint how to resolve an error of a GLSL or ShaderToy shader with command line options.
+/// `sdf_option` selects the SDF function if it cannot be detected.
+fn error_hint(err: &shadertoy::ShaderProcessingError, sdf_option: &str) -> String {
+    use shadertoy::ShaderProcessingError;
+    match err {
+        ShaderProcessingError::AmbiguousSdf(_) => {
+            format!(". Select one of them with `{sdf_option}`")
+        }
+        ShaderProcessingError::BufferInput { channel, .. } => format!(
+            ". Bind an image with `--channel{channel}` or select another render pass with `--shadertoy-pass`"
+        ),
+        ShaderProcessingError::UnsupportedChannel { channel, .. } => {
+            format!(". Bind an image with `--channel{channel}`")
+        }
+        _ => String::new(),
+    }
+}
 
-    if let Err(err) = mesh::TriangleMesh::from(vertex_items).write_to_file(&args.mesh) {
-        log::error!("Could not write mesh to {}!", err);
+/// Client for the ShaderToy API configured by the command line arguments and the environment
+fn shadertoy_client(
+    args: &Arguments,
+) -> Result<shadertoy::ShaderToyClient, shadertoy::ShaderProcessingError> {
+    let cache = match (&args.shadertoy_cache, args.no_shadertoy_cache) {
+        (_, true) => None,
+        (Some(dir), false) => Some(shadertoy::ShaderCache::new(dir)),
+        (None, false) => shadertoy::ShaderCache::from_env(),
+    };
+    let mut client = shadertoy::ShaderToyClient::new()?;
+    client
+        .set_from_env()
+        .set_retries(args.shadertoy_retries)
+        .set_cache(cache);
+    if let Some(api_url) = &args.shadertoy_api_url {
+        client.set_api_url(api_url);
+    }
+    if let Some(api_key) = &args.shadertoy_api_key {
+        client.set_api_key(api_key);
     }
+    Ok(client)
+}
 
-    log::info!("Mesh written to {}", args.mesh)
+fn shadertoy_options(args: &Arguments) -> shadertoy::ShaderToyOptions {
+    shadertoy::ShaderToyOptions {
+        pass: args.shadertoy_pass.clone(),
+        sdf: args.shadertoy_sdf.clone(),
+        channels: [
+            args.channel0.clone(),
+            args.channel1.clone(),
+            args.channel2.clone(),
+            args.channel3.clone(),
+        ],
+    }
 }
 
 #[tokio::main]
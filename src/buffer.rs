@@ -0,0 +1,198 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::ops::Range;
+
+use crate::param::{self, Param};
+
+/// A storage buffer with a staging buffer to read back (parts of) its content
+pub struct StorageBuffer<T: bytemuck::Pod> {
+    data: Vec<T>,
+    len: usize,
+    buffer: wgpu::Buffer,
+    staging_buffer: wgpu::Buffer,
+    staged: usize,
+    binding_id: u32,
+}
+
+impl<T: bytemuck::Pod> StorageBuffer<T> {
+    pub fn new(device: &wgpu::Device, len: usize, binding_id: u32) -> Self {
+        let size = Self::size_of(len);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            data: Vec::new(),
+            len,
+            buffer,
+            staging_buffer,
+            staged: 0,
+            binding_id,
+        }
+    }
+
+    fn size_of(len: usize) -> u64 {
+        (len.max(1) * std::mem::size_of::<T>()) as u64
+    }
+
+    /// Number of elements in the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Elements read back by the last call of `map_buffer`
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Recreate the buffer if it holds less than `len` elements.
+    /// Returns true if the buffer was recreated and bind groups need to be updated.
+    pub fn reserve(&mut self, device: &wgpu::Device, len: usize) -> bool {
+        if len <= self.len {
+            return false;
+        }
+
+        *self = Self::new(device, len.next_power_of_two(), self.binding_id);
+        true
+    }
+
+    pub fn bind_group_layout_entry(&self) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: self.binding_id,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    pub fn bind_group_entry(&self) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: self.binding_id,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    /// Copy the elements in `range` to the staging buffer
+    pub fn copy_buffer_to_staging(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        range: Range<usize>,
+    ) {
+        let element_size = std::mem::size_of::<T>() as u64;
+        self.staged = range.len();
+        if self.staged > 0 {
+            encoder.copy_buffer_to_buffer(
+                &self.buffer,
+                range.start as u64 * element_size,
+                &self.staging_buffer,
+                0,
+                self.staged as u64 * element_size,
+            );
+        }
+    }
+
+    pub async fn map_buffer(&mut self, device: &wgpu::Device) {
+        if self.staged == 0 {
+            self.data.clear();
+            return;
+        }
+
+        let buffer_slice = self
+            .staging_buffer
+            .slice(..(self.staged * std::mem::size_of::<T>()) as u64);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+        device.poll(wgpu::Maintain::Wait);
+        receiver.receive().await.unwrap().unwrap();
+        {
+            let view = buffer_slice.get_mapped_range();
+            self.data = Vec::from(bytemuck::cast_slice(&view[..]));
+        }
+        self.staging_buffer.unmap();
+    }
+}
+
+/// A uniform buffer with the values of the parameters declared with `@param(uniform)`.
+/// Values can be changed between jobs without recompiling the shader or rebuilding pipelines.
+pub struct ParamsBuffer {
+    params: Vec<Param>,
+    buffer: wgpu::Buffer,
+    binding_id: u32,
+}
+
+impl ParamsBuffer {
+    pub fn new(device: &wgpu::Device, params: &[Param], binding_id: u32) -> Self {
+        let params: Vec<Param> = params.iter().filter(|p| p.uniform).cloned().collect();
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: param::uniform_params_bytes(&params).len() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            params,
+            buffer,
+            binding_id,
+        }
+    }
+
+    /// Uniform parameters with their current values
+    pub fn params(&self) -> &[Param] {
+        &self.params
+    }
+
+    /// Set the value of a uniform parameter, the value is checked against the type and range of the parameter.
+    /// Call `write` to upload the values.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        self.params
+            .iter_mut()
+            .find(|p| p.name == name)
+            .ok_or(format!("Unknown uniform parameter `{name}`"))?
+            .set_value(value)
+    }
+
+    /// Upload the current values to the buffer
+    pub fn write(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, &param::uniform_params_bytes(&self.params));
+    }
+
+    pub fn bind_group_layout_entry(&self) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: self.binding_id,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    pub fn bind_group_entry(&self) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: self.binding_id,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Textures bound to the channels `iChannel0` to `iChannel3` of ShaderToy shaders
+
+/// Number of channels of a ShaderToy shader
+pub const CHANNEL_COUNT: usize = 4;
+
+/// Bind group of the channels.
+/// Channel `i` has its texture at binding `2 * i` and its sampler at binding `2 * i + 1`.
+pub const CHANNEL_GROUP: u32 = 2;
+
+/// Size of the header of a ShaderToy volume file
+const VOLUME_HEADER_SIZE: usize = 20;
+
+/// Texture filter of a channel
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    Nearest,
+    /// Linear filtering, also used for ShaderToy's `mipmap` filter because the SDF samples mip level 0
+    #[default]
+    Linear,
+}
+
+/// Wrap mode of a channel
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Wrap {
+    Clamp,
+    #[default]
+    Repeat,
+}
+
+/// RGBA image with 8 bits per component
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    /// Width, height and depth, the depth of 2D images is 1
+    pub size: [u32; 3],
+    /// The image is a volume and bound as 3D texture
+    pub volume: bool,
+    /// Pixels, row by row and slice by slice
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// Decode a PNG image. ShaderToy flips images vertically (`vflip`), so that the first row is at the bottom.
+    pub fn from_png(bytes: &[u8], vflip: bool) -> Result<Self, String> {
+        let mut decoder = ::png::Decoder::new(std::io::Cursor::new(bytes));
+        decoder.set_transformations(::png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buffer)
+            .map_err(|err| err.to_string())?;
+        buffer.truncate(info.buffer_size());
+
+        let data: Vec<u8> = match info.color_type {
+            ::png::ColorType::Rgba => buffer,
+            ::png::ColorType::Rgb => buffer
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            ::png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            ::png::ColorType::Grayscale => buffer.iter().flat_map(|g| [*g, *g, *g, 255]).collect(),
+            ::png::ColorType::Indexed => return Err("Indexed PNG images are not supported".into()),
+        };
+
+        let row = 4 * info.width as usize;
+        let data = match vflip {
+            true => data.chunks_exact(row).rev().flatten().copied().collect(),
+            false => data,
+        };
+
+        Ok(Self {
+            size: [info.width, info.height, 1],
+            volume: false,
+            data,
+        })
+    }
+
+    /// Read a ShaderToy volume (`.bin`).
+    /// The file has a header with a signature, the width, height and depth as `u32`,
+    /// the number of components as `u8`, the layout as `u8` and the format as `u16`,
+    /// followed by the voxels with 8 bits per component.
+    pub fn from_volume(bytes: &[u8]) -> Result<Self, String> {
+        let header = bytes
+            .get(..VOLUME_HEADER_SIZE)
+            .ok_or("The volume has no header")?;
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+        let size = [u32_at(4), u32_at(8), u32_at(12)];
+        let components = header[16] as usize;
+        let format = u16::from_le_bytes([header[18], header[19]]);
+
+        if format != 0 || !(1..=4).contains(&components) {
+            return Err(format!(
+                "Volumes with {components} components in format {format} are not supported, only 8 bit formats are"
+            ));
+        }
+        let voxels = bytes[VOLUME_HEADER_SIZE..].chunks_exact(components);
+        if voxels.len() != size.iter().map(|s| *s as usize).product::<usize>() {
+            return Err(format!(
+                "The size of the volume data does not match its size {}x{}x{}",
+                size[0], size[1], size[2]
+            ));
+        }
+
+        // Missing components are 0, alpha is 1, like in a texture with fewer components
+        let data = voxels
+            .flat_map(|v| {
+                let c = |i: usize, default: u8| v.get(i).copied().unwrap_or(default);
+                [c(0, 0), c(1, 0), c(2, 0), c(3, 255)]
+            })
+            .collect();
+
+        Ok(Self {
+            size,
+            volume: true,
+            data,
+        })
+    }
+
+    /// Decode a PNG image or a ShaderToy volume (`.bin`), depending on the extension of `name`
+    pub fn decode(name: &str, bytes: &[u8], vflip: bool) -> Result<Self, String> {
+        let extension = std::path::Path::new(name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Self::from_png(bytes, vflip),
+            "bin" => Self::from_volume(bytes),
+            _ => Err(format!(
+                "Cannot read `{name}`, only PNG images and ShaderToy volumes (.bin) are supported"
+            )),
+        }
+        .map_err(|err| format!("{name}: {err}"))
+    }
+}
+
+/// Image and sampler settings of a channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    pub image: Image,
+    pub filter: Filter,
+    pub wrap: Wrap,
+    /// The image is stored in sRGB color space and converted to linear color space when sampled
+    pub srgb: bool,
+}
+
+impl Channel {
+    /// Create a channel with a linear filter and repeat wrap mode
+    pub fn new(image: Image) -> Self {
+        Self {
+            image,
+            filter: Filter::default(),
+            wrap: Wrap::default(),
+            srgb: false,
+        }
+    }
+
+    /// GLSL declarations of the texture and the sampler of channel `index`, combined to `iChannel{index}`
+    pub fn glsl_declaration(&self, index: usize) -> String {
+        let dimension = match self.image.volume {
+            true => "3D",
+            false => "2D",
+        };
+        let binding = 2 * index;
+        format!(
+            "layout(set = {CHANNEL_GROUP}, binding = {binding}) uniform texture{dimension} iChannel{index}_texture;\n\
+             layout(set = {CHANNEL_GROUP}, binding = {}) uniform sampler iChannel{index}_sampler;\n\
+             #define iChannel{index} sampler{dimension}(iChannel{index}_texture, iChannel{index}_sampler)\n",
+            binding + 1
+        )
+    }
+}
+
+/// GLSL declarations of the channels and the constant `iChannelResolution` with the sizes of their images
+pub fn glsl_declarations(channels: &[Option<Channel>]) -> String {
+    let mut glsl = String::new();
+    let mut resolutions = Vec::new();
+    for index in 0..CHANNEL_COUNT {
+        let channel = channels.get(index).and_then(|channel| channel.as_ref());
+        if let Some(channel) = channel {
+            glsl += &channel.glsl_declaration(index);
+        }
+        let [w, h, d] = channel.map_or([0; 3], |channel| channel.image.size);
+        resolutions.push(format!("vec3({w}.0, {h}.0, {d}.0)"));
+    }
+    glsl += &format!(
+        "const vec3 iChannelResolution[{CHANNEL_COUNT}] = vec3[{CHANNEL_COUNT}]({});\n",
+        resolutions.join(", ")
+    );
+    glsl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        // 2x2 RGB image, the top row is red, the bottom row is green
+        let mut png = Vec::new();
+        let mut encoder = ::png::Encoder::new(&mut png, 2, 2);
+        encoder.set_color(::png::ColorType::Rgb);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_image_data(&[255, 0, 0, 255, 0, 0, 0, 255, 0, 0, 255, 0])
+            .unwrap();
+        writer.finish().unwrap();
+
+        let image = Image::decode("noise.png", &png, false).unwrap();
+        assert_eq!(image.size, [2, 2, 1]);
+        assert!(!image.volume);
+        assert_eq!(image.data[..4], [255, 0, 0, 255]);
+        let image = Image::decode("NOISE.PNG", &png, true).unwrap();
+        assert_eq!(image.data[..4], [0, 255, 0, 255]);
+
+        // 2x1x2 volume with one component
+        let mut volume = b"BIN\0".to_vec();
+        for v in [2u32, 1, 2] {
+            volume.extend(v.to_le_bytes());
+        }
+        volume.extend([1, 0, 0, 0]);
+        volume.extend([10, 20, 30, 40]);
+        let image = Image::decode("noise.bin", &volume, true).unwrap();
+        assert_eq!(image.size, [2, 1, 2]);
+        assert!(image.volume);
+        assert_eq!(image.data[4..8], [20, 0, 0, 255]);
+        assert!(Image::decode("noise.bin", &volume[..22], false).is_err());
+
+        assert!(Image::decode("noise.jpg", &png, false)
+            .unwrap_err()
+            .contains("only PNG images"));
+    }
+
+    #[test]
+    fn declarations() {
+        let mut volume = Channel::new(Image {
+            size: [32, 32, 32],
+            volume: true,
+            data: vec![0; 32 * 32 * 32 * 4],
+        });
+        volume.filter = Filter::Nearest;
+        let glsl = glsl_declarations(&[None, Some(volume)]);
+        assert!(glsl.contains(
+            "layout(set = 2, binding = 2) uniform texture3D iChannel1_texture;\n\
+             layout(set = 2, binding = 3) uniform sampler iChannel1_sampler;\n\
+             #define iChannel1 sampler3D(iChannel1_texture, iChannel1_sampler)\n"
+        ));
+        assert!(!glsl.contains("iChannel0"));
+        assert!(glsl.contains("vec3(0.0, 0.0, 0.0), vec3(32.0, 32.0, 32.0), vec3(0.0, 0.0, 0.0)"));
+    }
+}
@@ -176,6 +176,7 @@ impl<T: Copy> Edge<T> {
     }
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Triangle<T: Copy>(pub T, pub T, pub T);
 
 impl Triangle<Vertex> {
@@ -184,7 +185,7 @@ impl Triangle<Vertex> {
     }
 }
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, PartialEq)]
 pub struct Quad<T: Copy>(pub T, pub T, pub T, pub T);
 
 impl<T: Copy> Quad<T> {
@@ -196,24 +197,102 @@ impl<T: Copy> Quad<T> {
         }
     }
 
+    /// Split the quad along the diagonal between the first and the third vertex
     pub fn make_triangles(&self) -> (Triangle<T>, Triangle<T>) {
         (
             Triangle(self.2, self.1, self.0),
             Triangle(self.0, self.3, self.2),
         )
     }
+
+    /// Split the quad along the diagonal between the second and the fourth vertex
+    pub fn make_triangles_alt(&self) -> (Triangle<T>, Triangle<T>) {
+        (
+            Triangle(self.3, self.2, self.1),
+            Triangle(self.1, self.0, self.3),
+        )
+    }
+}
+
+impl Quad<Vertex> {
+    /// Squared lengths of the diagonals 0-2 and 1-3
+    fn diagonals(&self) -> (Scalar, Scalar) {
+        (
+            (self.2.pos - self.0.pos).square_length(),
+            (self.3.pos - self.1.pos).square_length(),
+        )
+    }
+
+    /// How well the face normals of two triangles agree with their vertex normals
+    fn normal_agreement(tris: &(Triangle<Vertex>, Triangle<Vertex>)) -> Scalar {
+        let agreement = |t: &Triangle<Vertex>| {
+            // Triangles are wound counter-clockwise when seen from outside
+            let n = (t.1.pos - t.0.pos).cross(t.2.pos - t.0.pos);
+            let len = n.length();
+            if len <= 0.0 {
+                return 0.0;
+            }
+            (t.0.normal + t.1.normal + t.2.normal).dot(n / len)
+        };
+        agreement(&tris.0) + agreement(&tris.1)
+    }
+
+    /// Returns true if the quad should be split with `make_triangles_alt`
+    pub fn prefers_alt_split(&self, triangulation: mesh::Triangulation) -> bool {
+        use mesh::Triangulation;
+        match triangulation {
+            Triangulation::Fixed | Triangulation::Quads => false,
+            Triangulation::ShortestDiagonal => {
+                let (d02, d13) = self.diagonals();
+                d13 < d02
+            }
+            Triangulation::Normals => {
+                Self::normal_agreement(&self.make_triangles_alt())
+                    > Self::normal_agreement(&self.make_triangles())
+            }
+        }
+    }
 }
 
 impl Quad<u32> {
     pub fn is_valid(&self) -> bool {
         self.0 != u32::MAX && self.1 != u32::MAX && self.2 != u32::MAX && self.3 != u32::MAX
     }
+
+    /// Split the quad into two triangles, choosing the diagonal by `triangulation`
+    pub fn triangulate(
+        &self,
+        vertices: &[Vertex],
+        triangulation: mesh::Triangulation,
+    ) -> (Triangle<u32>, Triangle<u32>) {
+        let quad = Quad(
+            vertices[self.0 as usize],
+            vertices[self.1 as usize],
+            vertices[self.2 as usize],
+            vertices[self.3 as usize],
+        );
+        if quad.prefers_alt_split(triangulation) {
+            self.make_triangles_alt()
+        } else {
+            self.make_triangles()
+        }
+    }
 }
 
+pub mod buffer;
+pub mod channel;
 pub mod mesh;
+pub mod param;
 pub mod png;
+pub mod sdf;
+pub mod sdf3d;
 pub mod shader;
+pub mod source_map;
+pub mod text;
 pub mod texture;
 
+#[cfg(test)]
+mod testing;
+
 #[cfg(feature = "shadertoy")]
 pub mod shadertoy;
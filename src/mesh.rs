@@ -138,27 +138,155 @@ impl<'a> PLYWriter<'a> {
         }
         Ok(())
     }
+
+    /// Write a quad face, wound like the triangles of [`Quad::make_triangles`]
+    pub fn quad_face(&mut self, quad: &Quad<u32>) -> std::io::Result<()> {
+        writeln!(
+            &mut self.writer,
+            "4 {} {} {} {}",
+            quad.3, quad.2, quad.1, quad.0
+        )?;
+        Ok(())
+    }
+
+    pub fn quad_faces(&mut self, quad_faces: &[Quad<u32>]) -> std::io::Result<()> {
+        for face in quad_faces {
+            self.quad_face(face)?;
+        }
+        Ok(())
+    }
+}
+
+/// How the quads generated by dual contouring are split into triangles
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Triangulation {
+    /// Always split along the same diagonal
+    #[default]
+    Fixed,
+    /// Split along the shorter diagonal
+    ShortestDiagonal,
+    /// Split along the diagonal whose triangles agree best with the vertex normals
+    Normals,
+    /// Keep quads untriangulated, for output formats that support them
+    Quads,
 }
 
 #[derive(Default)]
 pub struct TriangleMesh {
     vertices: Vec<Vertex>,
     triangle_indices: Vec<Triangle<u32>>,
+    quad_indices: Vec<Quad<u32>>,
 }
 
 impl TriangleMesh {
+    /// Create a triangle mesh from a vertex list
+    pub fn from_vertex_list(l: VertexList, triangulation: Triangulation) -> Self {
+        let mut mesh = TriangleMesh {
+            vertices: l.fetch_vertices(),
+            ..Default::default()
+        };
+        for quad in l.fetch_quad_indices() {
+            mesh.add_quad(quad, triangulation);
+        }
+        mesh
+    }
+
+    /// Create a triangle mesh from a vertex list, quads are assembled in parallel
+    pub fn from_vertex_list_parallel(l: VertexList, triangulation: Triangulation) -> Self {
+        let mut mesh = TriangleMesh {
+            vertices: l.fetch_vertices(),
+            ..Default::default()
+        };
+        for quad in l.fetch_quad_indices_parallel() {
+            mesh.add_quad(quad, triangulation);
+        }
+        mesh
+    }
+
     pub fn clear(&mut self) {
         self.vertices.clear();
         self.triangle_indices.clear();
+        self.quad_indices.clear();
+    }
+
+    /// Append the vertices and faces of another mesh, e.g. to write multiple bodies into one file
+    pub fn append(&mut self, other: TriangleMesh) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.triangle_indices.extend(
+            other
+                .triangle_indices
+                .into_iter()
+                .map(|t| Triangle(t.0 + offset, t.1 + offset, t.2 + offset)),
+        );
+        self.quad_indices.extend(
+            other
+                .quad_indices
+                .into_iter()
+                .map(|q| Quad(q.0 + offset, q.1 + offset, q.2 + offset, q.3 + offset)),
+        );
+    }
+
+    pub fn num_vertices(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.triangle_indices.len()
+    }
+
+    pub fn num_quads(&self) -> usize {
+        self.quad_indices.len()
+    }
+
+    pub fn add_vertex(&mut self, vertex: Vertex) {
+        self.vertices.push(vertex);
+    }
+
+    /// Add a quad, which is split into triangles unless `triangulation` is `Triangulation::Quads`.
+    /// The vertices of the quad must have been added before.
+    pub fn add_quad(&mut self, quad: Quad<u32>, triangulation: Triangulation) {
+        if triangulation == Triangulation::Quads {
+            self.quad_indices.push(quad);
+        } else {
+            let tris = quad.triangulate(&self.vertices, triangulation);
+            self.triangle_indices.push(tris.0);
+            self.triangle_indices.push(tris.1);
+        }
     }
 
+    /// Add quads from an index buffer with four indices per quad.
+    /// Quads with an invalid index (`u32::MAX`) are skipped.
+    pub fn add_quad_indices(&mut self, indices: &[u32], triangulation: Triangulation) {
+        for q in indices.chunks_exact(4) {
+            let quad = Quad(q[0], q[1], q[2], q[3]);
+            if quad.is_valid() {
+                self.add_quad(quad, triangulation);
+            } else {
+                log::warn!("Invalid quad: {:?}. Mesh will not be water-tight!", quad);
+            }
+        }
+    }
+
+    /// Returns all triangles, quads are split along a fixed diagonal
     pub fn fetch_triangles(&self) -> Vec<Triangle<Vertex>> {
-        let mut triangles = Vec::with_capacity(self.triangle_indices.len());
+        let mut triangles =
+            Vec::with_capacity(self.triangle_indices.len() + self.quad_indices.len() * 2);
+        let vertex = |i: u32| self.vertices[i as usize];
         for t in &self.triangle_indices {
+            triangles.push(Triangle(vertex(t.0), vertex(t.1), vertex(t.2)));
+        }
+        for q in &self.quad_indices {
+            let tris = q.make_triangles();
+            triangles.push(Triangle(
+                vertex(tris.0 .0),
+                vertex(tris.0 .1),
+                vertex(tris.0 .2),
+            ));
             triangles.push(Triangle(
-                self.vertices[t.0 as usize],
-                self.vertices[t.1 as usize],
-                self.vertices[t.2 as usize],
+                vertex(tris.1 .0),
+                vertex(tris.1 .1),
+                vertex(tris.1 .2),
             ));
         }
         triangles
@@ -170,6 +298,10 @@ impl TriangleMesh {
         let mut f = std::io::BufWriter::new(File::create(path)?);
         let mut stl_writer = STLWriter::new(&mut f);
 
+        if !self.quad_indices.is_empty() {
+            log::warn!("STL does not support quads, quads are written as triangles.");
+        }
+
         let triangles = self.fetch_triangles();
 
         for triangle in &triangles {
@@ -202,11 +334,12 @@ impl TriangleMesh {
         let mut ply_writer = PLYWriter::new(&mut ply_f)?;
 
         ply_writer.header_element_vertex3d(self.vertices.len())?;
-        ply_writer.header_element_face(self.triangle_indices.len())?;
+        ply_writer.header_element_face(self.triangle_indices.len() + self.quad_indices.len())?;
         ply_writer.header_end()?;
 
         ply_writer.vertices(&self.vertices)?;
-        ply_writer.tri_faces(&self.triangle_indices)
+        ply_writer.tri_faces(&self.triangle_indices)?;
+        ply_writer.quad_faces(&self.quad_indices)
     }
 }
 
@@ -265,62 +398,159 @@ impl VertexList {
     }
 
     pub fn fetch_triangle_indices(&self) -> Vec<Triangle<u32>> {
-        let mut indices = Vec::with_capacity(self.0.len() * 2);
+        Self::split_quads(self.fetch_quad_indices())
+    }
+
+    /// Same as `fetch_triangle_indices`, but the slices are processed in parallel.
+    pub fn fetch_triangle_indices_parallel(&self) -> Vec<Triangle<u32>> {
+        Self::split_quads(self.fetch_quad_indices_parallel())
+    }
+
+    fn split_quads(quads: Vec<Quad<u32>>) -> Vec<Triangle<u32>> {
+        let mut indices = Vec::with_capacity(quads.len() * 2);
+        for quad in quads {
+            let tris = quad.make_triangles();
+            indices.push(tris.0);
+            indices.push(tris.1);
+        }
+        indices
+    }
 
-        let push_triangles = |indices: &mut Vec<Triangle<u32>>, quad: Quad<u32>| {
+    pub fn fetch_quad_indices(&self) -> Vec<Quad<u32>> {
+        let mut indices = Vec::with_capacity(self.0.len());
+
+        for vertex_item in &self.0 {
+            Self::push_quads(vertex_item, &mut indices, |x, y, z| {
+                self.vertex_index(x, y, z)
+            });
+        }
+
+        indices
+    }
+
+    /// Same as `fetch_quad_indices`, but the slices are processed in parallel.
+    /// Each thread looks up vertex indices in a dense index grid of the current and the previous slice.
+    pub fn fetch_quad_indices_parallel(&self) -> Vec<Quad<u32>> {
+        use rayon::prelude::*;
+
+        let Some(last) = self.0.last() else {
+            return Vec::new();
+        };
+
+        // Vertex items are sorted by z, y and x
+        let slices: Vec<std::ops::Range<usize>> = (0..=last.cell.2)
+            .map(|z| {
+                let start = self.0.partition_point(|item| item.cell.2 < z);
+                let end = self.0.partition_point(|item| item.cell.2 <= z);
+                start..end
+            })
+            .collect();
+
+        let (width, height) = self.0.iter().fold((0, 0), |(w, h), item| {
+            (
+                w.max(item.cell.0 as usize + 1),
+                h.max(item.cell.1 as usize + 1),
+            )
+        });
+
+        let fill_grid = |grid: &mut Vec<u32>, slice: &std::ops::Range<usize>| {
+            grid.fill(u32::MAX);
+            for idx in slice.clone() {
+                let (x, y, _) = self.0[idx].cell;
+                grid[y as usize * width + x as usize] = idx as u32;
+            }
+        };
+
+        let slices_per_chunk = slices.len().div_ceil(rayon::current_num_threads() * 4);
+
+        slices
+            .par_chunks(slices_per_chunk)
+            .enumerate()
+            .map(|(chunk_idx, chunk)| {
+                let first_z = chunk_idx * slices_per_chunk;
+                let mut prev_grid = vec![u32::MAX; width * height];
+                let mut grid = vec![u32::MAX; width * height];
+                if first_z > 0 {
+                    fill_grid(&mut prev_grid, &slices[first_z - 1]);
+                }
+
+                let mut indices = Vec::new();
+                for (i, slice) in chunk.iter().enumerate() {
+                    let z = (first_z + i) as u16;
+                    fill_grid(&mut grid, slice);
+
+                    for vertex_item in &self.0[slice.clone()] {
+                        Self::push_quads(vertex_item, &mut indices, |x, y, cz| {
+                            let grid = if cz == z { &grid } else { &prev_grid };
+                            grid[y as usize * width + x as usize]
+                        });
+                    }
+
+                    std::mem::swap(&mut grid, &mut prev_grid);
+                }
+                indices
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Push the quads generated by a vertex item.
+    /// `vertex_index` returns the index of the vertex in cell (x, y, z) or u32::MAX if not found.
+    fn push_quads(
+        vertex_item: &VertexListItem,
+        indices: &mut Vec<Quad<u32>>,
+        vertex_index: impl Fn(u16, u16, u16) -> u32,
+    ) {
+        let mut push_quad = |quad: Quad<u32>| {
             if quad.is_valid() {
-                let tris = quad.make_triangles();
-                indices.push(tris.0);
-                indices.push(tris.1)
+                indices.push(quad);
             } else {
                 log::warn!("Invalid quad: {:?}. Mesh will not be water-tight!", quad);
             }
         };
 
-        for vertex_item in &self.0 {
-            let changes = vertex_item.sign_changes;
-            let x = vertex_item.cell.0;
-            let y = vertex_item.cell.1;
-            let z = vertex_item.cell.2;
-
-            if changes.0 != changes.3 && y > 0 && z > 0 {
-                let quad = Quad(
-                    self.vertex_index(x, y - 1, z - 1),
-                    self.vertex_index(x, y, z - 1),
-                    self.vertex_index(x, y, z),
-                    self.vertex_index(x, y - 1, z),
-                )
-                .swap(changes.0);
-
-                push_triangles(&mut indices, quad);
-            }
+        let changes = vertex_item.sign_changes;
+        let x = vertex_item.cell.0;
+        let y = vertex_item.cell.1;
+        let z = vertex_item.cell.2;
+
+        if changes.0 != changes.3 && y > 0 && z > 0 {
+            let quad = Quad(
+                vertex_index(x, y - 1, z - 1),
+                vertex_index(x, y, z - 1),
+                vertex_index(x, y, z),
+                vertex_index(x, y - 1, z),
+            )
+            .swap(changes.0);
+
+            push_quad(quad);
+        }
 
-            if changes.1 != changes.3 && x > 0 && z > 0 {
-                let quad = Quad(
-                    self.vertex_index(x - 1, y, z - 1),
-                    self.vertex_index(x, y, z - 1),
-                    self.vertex_index(x, y, z),
-                    self.vertex_index(x - 1, y, z),
-                )
-                .swap(!changes.1);
+        if changes.1 != changes.3 && x > 0 && z > 0 {
+            let quad = Quad(
+                vertex_index(x - 1, y, z - 1),
+                vertex_index(x, y, z - 1),
+                vertex_index(x, y, z),
+                vertex_index(x - 1, y, z),
+            )
+            .swap(!changes.1);
 
-                push_triangles(&mut indices, quad);
-            }
+            push_quad(quad);
+        }
 
-            if changes.2 != changes.3 && x > 0 && y > 0 {
-                let quad = Quad(
-                    self.vertex_index(x - 1, y - 1, z),
-                    self.vertex_index(x, y - 1, z),
-                    self.vertex_index(x, y, z),
-                    self.vertex_index(x - 1, y, z),
-                )
-                .swap(changes.2);
+        if changes.2 != changes.3 && x > 0 && y > 0 {
+            let quad = Quad(
+                vertex_index(x - 1, y - 1, z),
+                vertex_index(x, y - 1, z),
+                vertex_index(x, y, z),
+                vertex_index(x - 1, y, z),
+            )
+            .swap(changes.2);
 
-                push_triangles(&mut indices, quad);
-            }
+            push_quad(quad);
         }
-
-        indices
     }
 
     /// Returns the index of the vertex at the given position or u32::MAX if not found
@@ -333,9 +563,174 @@ impl VertexList {
 
 impl From<VertexList> for TriangleMesh {
     fn from(l: VertexList) -> Self {
-        TriangleMesh {
-            vertices: l.fetch_vertices(),
-            triangle_indices: l.fetch_triangle_indices(),
+        TriangleMesh::from_vertex_list(l, Triangulation::Fixed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Vertex list of a sphere, with sign changes computed like in `dualcontour.wgsl`
+    fn sphere_vertex_list(res: u16) -> VertexList {
+        let sdf = |x: u16, y: u16, z: u16| {
+            let p = Vec3D::new(x as f32, y as f32, z as f32) / (res - 1) as f32 * 2.0
+                - Vec3D::splat(1.0);
+            p.length() - 0.7
+        };
+
+        let mut list = VertexList::default();
+        for z in 0..res {
+            for y in 0..res {
+                for x in 0..res {
+                    let corners = [
+                        sdf(x, y, z),
+                        sdf(x + 1, y, z),
+                        sdf(x, y + 1, z),
+                        sdf(x, y, z + 1),
+                    ];
+                    let inside = corners.iter().filter(|d| **d > 0.0).count();
+                    if inside == 0 || inside == corners.len() {
+                        continue;
+                    }
+
+                    let vertex = Vertex {
+                        pos: Vec3D::new(x as f32, y as f32, z as f32),
+                        normal: Vec3D::zero(),
+                    };
+                    let sign_changes = (
+                        corners[1] > 0.0,
+                        corners[2] > 0.0,
+                        corners[3] > 0.0,
+                        corners[0] > 0.0,
+                    );
+                    list.insert((x, y, z), sign_changes, vertex);
+                }
+            }
         }
+        list
+    }
+
+    #[test]
+    fn parallel_triangle_indices() {
+        let list = sphere_vertex_list(48);
+        let serial = list.fetch_triangle_indices();
+        let parallel = list.fetch_triangle_indices_parallel();
+
+        assert!(!serial.is_empty());
+        assert_eq!(serial, parallel);
+    }
+
+    #[test]
+    fn parallel_triangle_indices_empty() {
+        assert!(VertexList::default()
+            .fetch_triangle_indices_parallel()
+            .is_empty());
+    }
+
+    /// Saddle quad whose diagonal 1-3 is shorter than 0-2
+    fn saddle_vertices() -> Vec<Vertex> {
+        let normal = Vec3D::new(0.0, 0.0, 1.0);
+        [
+            Vec3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(0.1, 0.9, 0.5),
+            Vec3D::new(1.0, 1.0, 0.0),
+            Vec3D::new(0.9, 0.1, 0.5),
+        ]
+        .into_iter()
+        .map(|pos| Vertex { pos, normal })
+        .collect()
+    }
+
+    #[test]
+    fn triangulation_shortest_diagonal() {
+        let vertices = saddle_vertices();
+        let quad = Quad(0, 1, 2, 3);
+
+        assert_eq!(
+            quad.triangulate(&vertices, Triangulation::Fixed),
+            quad.make_triangles()
+        );
+        assert_eq!(
+            quad.triangulate(&vertices, Triangulation::ShortestDiagonal),
+            quad.make_triangles_alt()
+        );
+    }
+
+    #[test]
+    fn triangulation_normals() {
+        let mut vertices = saddle_vertices();
+        let quad = Quad(0, 1, 2, 3);
+
+        // Vertex normals tilted like the triangles of the 0-2 split
+        vertices[1].normal = Vec3D::new(0.5, -0.5, 1.0).normalize();
+        vertices[3].normal = Vec3D::new(-0.5, 0.5, 1.0).normalize();
+        assert_eq!(
+            quad.triangulate(&vertices, Triangulation::Normals),
+            quad.make_triangles()
+        );
+
+        // Vertex normals tilted like the triangles of the 1-3 split
+        vertices[0].normal = Vec3D::new(-0.5, -0.5, 1.0).normalize();
+        vertices[1].normal = Vec3D::new(0.0, 0.0, 1.0);
+        vertices[2].normal = Vec3D::new(0.5, 0.5, 1.0).normalize();
+        vertices[3].normal = Vec3D::new(0.0, 0.0, 1.0);
+        assert_eq!(
+            quad.triangulate(&vertices, Triangulation::Normals),
+            quad.make_triangles_alt()
+        );
+    }
+
+    #[test]
+    fn quads_untriangulated() {
+        let mut mesh = TriangleMesh::default();
+        for vertex in saddle_vertices() {
+            mesh.add_vertex(vertex);
+        }
+        mesh.add_quad_indices(&[0, 1, 2, 3, 0, 1, 2, u32::MAX], Triangulation::Quads);
+
+        assert_eq!(mesh.num_quads(), 1);
+        assert_eq!(mesh.num_triangles(), 0);
+        assert_eq!(mesh.fetch_triangles().len(), 2);
+
+        // The written quad must face the same way as its triangles
+        let mut buf = Vec::new();
+        PLYWriter::new(&mut buf)
+            .unwrap()
+            .quad_faces(&mesh.quad_indices)
+            .unwrap();
+        let face: Vec<usize> = String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .last()
+            .unwrap()
+            .split_whitespace()
+            .skip(1)
+            .map(|i| i.parse().unwrap())
+            .collect();
+        let pos = |i: usize| mesh.vertices[i].pos;
+        let quad_normal = (pos(face[2]) - pos(face[0])).cross(pos(face[3]) - pos(face[1]));
+        for t in mesh.fetch_triangles() {
+            let normal = (t.1.pos - t.0.pos).cross(t.2.pos - t.0.pos);
+            assert!(quad_normal.dot(normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn append_offsets_indices() {
+        let mut mesh = TriangleMesh::default();
+        let mut other = TriangleMesh::default();
+        for vertex in saddle_vertices() {
+            mesh.add_vertex(vertex);
+            other.add_vertex(vertex);
+        }
+        mesh.add_quad(Quad(0, 1, 2, 3), Triangulation::Fixed);
+        other.add_quad(Quad(0, 1, 2, 3), Triangulation::Quads);
+
+        mesh.append(other);
+
+        assert_eq!(mesh.num_vertices(), 8);
+        assert_eq!(mesh.num_triangles(), 2);
+        assert_eq!(mesh.quad_indices[0], Quad(4, 5, 6, 7));
     }
 }
@@ -0,0 +1,464 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Compile-time parameters of SDF files.
+//!
+//! A parameter is a `const` annotated with `@param`, optionally with a range and a description:
+//!
+//! ```wgsl
+//! @param(min = 10.0, max = 20.0) const KEY_SIZE: f32 = 15.0; // Size of the key
+//! ```
+//!
+//! The value of a parameter can be overridden before the shader is compiled.
+//!
+//! Parameters declared with `@param(uniform)` are not compiled into the shader.
+//! They are members of the generated uniform struct `params` instead and are accessed like `params.KEY_SIZE`,
+//! so their values can be changed without recompiling the shader.
+
+/// Type of a parameter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamType {
+    F32,
+    I32,
+    U32,
+    Bool,
+}
+
+impl ParamType {
+    /// Parse a WGSL type name
+    fn from_wgsl(name: &str) -> Option<Self> {
+        match name {
+            "f32" => Some(Self::F32),
+            "i32" => Some(Self::I32),
+            "u32" => Some(Self::U32),
+            "bool" => Some(Self::Bool),
+            _ => None,
+        }
+    }
+
+    /// Type of a parameter without explicit type, inferred from its default value
+    fn infer(value: &str) -> Self {
+        match value {
+            "true" | "false" => Self::Bool,
+            _ if value.ends_with('u') => Self::U32,
+            _ if value.ends_with('i') => Self::I32,
+            // `e` and `f` are hexadecimal digits, hexadecimal floats have a `.` or a `p` exponent
+            _ if is_hex(value) => match value.contains(['.', 'p', 'P']) {
+                true => Self::F32,
+                false => Self::I32,
+            },
+            _ if value.contains(['.', 'e', 'f']) => Self::F32,
+            _ => Self::I32,
+        }
+    }
+
+    /// Parse a value of this type
+    pub fn parse(&self, value: &str) -> Result<ParamValue, String> {
+        let value = value.trim();
+        match self {
+            Self::F32 => parse_float(value)
+                .filter(|v| v.is_finite())
+                .map(ParamValue::F32),
+            Self::I32 => parse_integer(value.trim_end_matches('i'))
+                .and_then(|v| v.try_into().ok())
+                .map(ParamValue::I32),
+            Self::U32 => parse_integer(value.trim_end_matches('u'))
+                .and_then(|v| v.try_into().ok())
+                .map(ParamValue::U32),
+            Self::Bool => value.parse().ok().map(ParamValue::Bool),
+        }
+        .ok_or_else(|| format!("`{value}` is not a valid {self} value"))
+    }
+}
+
+/// The literal is hexadecimal, e.g. `0xff` or `-0x1p4`
+fn is_hex(value: &str) -> bool {
+    let digits = value.trim_start_matches('-');
+    digits.starts_with("0x") || digits.starts_with("0X")
+}
+
+/// Parse a decimal or hexadecimal integer literal without suffix
+fn parse_integer(value: &str) -> Option<i64> {
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, value),
+    };
+    let v = match is_hex(digits) {
+        true => i64::from_str_radix(&digits[2..], 16).ok()?,
+        false => digits.parse().ok()?,
+    };
+    Some(sign * v)
+}
+
+/// Parse a decimal or hexadecimal float literal with optional `f` suffix
+fn parse_float(value: &str) -> Option<f32> {
+    match is_hex(value) {
+        true => parse_hex_float(value).map(|v| v as f32),
+        false => value.trim_end_matches('f').parse().ok(),
+    }
+}
+
+/// Parse a hexadecimal float literal like `0x1.8p-2`, which is `0x18` scaled by `2^(-2 - 4)`.
+/// The `f` suffix is only allowed after an exponent, otherwise it is a digit.
+fn parse_hex_float(value: &str) -> Option<f64> {
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, value),
+    };
+    let (mantissa, exponent) = match digits[2..].split_once(['p', 'P']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.trim_end_matches('f').parse().ok()?),
+        None => (&digits[2..], 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let mut v = 0.0;
+    for c in integer.chars().chain(fraction.chars()) {
+        v = v * 16.0 + c.to_digit(16)? as f64;
+    }
+    Some(sign * v * 2f64.powi(exponent - 4 * fraction.len() as i32))
+}
+
+impl std::fmt::Display for ParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::F32 => write!(f, "f32"),
+            Self::I32 => write!(f, "i32"),
+            Self::U32 => write!(f, "u32"),
+            Self::Bool => write!(f, "bool"),
+        }
+    }
+}
+
+/// Value of a parameter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamValue {
+    F32(f32),
+    I32(i32),
+    U32(u32),
+    Bool(bool),
+}
+
+impl ParamValue {
+    /// Numeric value to check ranges, `None` for booleans
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::F32(v) => Some(*v as f64),
+            Self::I32(v) => Some(*v as f64),
+            Self::U32(v) => Some(*v as f64),
+            Self::Bool(_) => None,
+        }
+    }
+}
+
+/// Displays the value as WGSL literal
+impl std::fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::F32(v) => write!(f, "{v:?}"),
+            Self::I32(v) => write!(f, "{v}"),
+            Self::U32(v) => write!(f, "{v}u"),
+            Self::Bool(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+/// A compile-time parameter
+#[derive(Clone, Debug, PartialEq)]
+pub struct Param {
+    /// Name of the constant, e.g. `KEY_SIZE`
+    pub name: String,
+    /// Type of the parameter
+    pub ty: ParamType,
+    /// Value in the source file
+    pub default: ParamValue,
+    /// Value used in the shader, either the default or an override
+    pub value: ParamValue,
+    /// Minimum value
+    pub min: Option<ParamValue>,
+    /// Maximum value
+    pub max: Option<ParamValue>,
+    /// Description from the comment after the declaration
+    pub description: String,
+    /// The parameter is a member of the uniform struct `params` instead of a constant
+    pub uniform: bool,
+}
+
+impl Param {
+    /// Parse a parameter declaration, returns `None` if the line is not a declaration
+    pub fn parse(line: &str) -> Option<Result<Self, String>> {
+        let declaration = line.trim().strip_prefix("@param")?;
+        Some(Self::parse_declaration(declaration))
+    }
+
+    fn parse_declaration(declaration: &str) -> Result<Self, String> {
+        let declaration = declaration.trim_start();
+        let (arguments, declaration) = match declaration.strip_prefix('(') {
+            Some(rest) => rest
+                .split_once(')')
+                .ok_or("Missing `)` after @param arguments")?,
+            None => ("", declaration),
+        };
+
+        let declaration = declaration
+            .trim_start()
+            .strip_prefix("const ")
+            .ok_or("Expected `const` after @param")?;
+        let (declaration, description) = declaration
+            .split_once(';')
+            .ok_or("Missing `;` after parameter")?;
+        let description = description
+            .trim()
+            .trim_start_matches('/')
+            .trim()
+            .to_string();
+        let (name, default) = declaration
+            .split_once('=')
+            .ok_or("Parameter needs a default value")?;
+        let default = default.trim();
+
+        let (name, ty) = match name.split_once(':') {
+            Some((name, ty)) => (
+                name.trim(),
+                ParamType::from_wgsl(ty.trim())
+                    .ok_or(format!("Unsupported parameter type `{}`", ty.trim()))?,
+            ),
+            None => (name.trim(), ParamType::infer(default)),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("Invalid parameter name `{name}`"));
+        }
+
+        let default = ty.parse(default)?;
+        let mut param = Self {
+            name: name.to_string(),
+            ty,
+            default,
+            value: default,
+            min: None,
+            max: None,
+            description,
+            uniform: false,
+        };
+
+        for argument in arguments.split(',').filter(|a| !a.trim().is_empty()) {
+            if argument.trim() == "uniform" {
+                if ty == ParamType::Bool {
+                    return Err("bool parameters cannot be uniform, use u32 instead".into());
+                }
+                param.uniform = true;
+                continue;
+            }
+
+            let (key, value) = argument
+                .split_once('=')
+                .ok_or(format!("Expected `key = value` in `{}`", argument.trim()))?;
+            match key.trim() {
+                "min" => param.min = Some(ty.parse(value)?),
+                "max" => param.max = Some(ty.parse(value)?),
+                key => return Err(format!("Unknown @param argument `{key}`")),
+            }
+        }
+
+        param.check_range(&default)?;
+        Ok(param)
+    }
+
+    fn check_range(&self, value: &ParamValue) -> Result<(), String> {
+        let v = value.as_f64();
+        if let Some(min) = &self.min {
+            if v < min.as_f64() {
+                return Err(format!("{value} is less than the minimum {min}"));
+            }
+        }
+        if let Some(max) = &self.max {
+            if v > max.as_f64() {
+                return Err(format!("{value} is greater than the maximum {max}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Override the value with a value parsed from a string, e.g. from the command line
+    pub fn set_value(&mut self, value: &str) -> Result<(), String> {
+        let value = self.ty.parse(value)?;
+        self.check_range(&value)?;
+        self.value = value;
+        Ok(())
+    }
+
+    /// WGSL declaration of the constant with its current value
+    pub fn declaration(&self) -> String {
+        format!("const {}: {} = {};", self.name, self.ty, self.value)
+    }
+
+    /// Current value in the memory layout of a uniform buffer
+    fn uniform_bytes(&self) -> [u8; 4] {
+        match self.value {
+            ParamValue::F32(v) => v.to_le_bytes(),
+            ParamValue::I32(v) => v.to_le_bytes(),
+            ParamValue::U32(v) => v.to_le_bytes(),
+            ParamValue::Bool(v) => (v as u32).to_le_bytes(),
+        }
+    }
+}
+
+/// WGSL declaration of the struct `Params` with the uniform parameters and the variable `params`.
+/// Returns `None` if there are no uniform parameters.
+pub fn uniform_params_wgsl(params: &[Param], group: u32, binding: u32) -> Option<String> {
+    let members: String = params
+        .iter()
+        .filter(|param| param.uniform)
+        .map(|param| format!("    {}: {},\n", param.name, param.ty))
+        .collect();
+    if members.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "struct Params {{\n{members}}}\n\n@group({group})\n@binding({binding})\nvar<uniform> params: Params;\n"
+    ))
+}
+
+/// Values of the uniform parameters in the memory layout of the struct `Params`.
+/// All members are 4-byte scalars, the size is padded to 16 bytes.
+pub fn uniform_params_bytes(params: &[Param]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = params
+        .iter()
+        .filter(|param| param.uniform)
+        .flat_map(|param| param.uniform_bytes())
+        .collect();
+    bytes.resize(bytes.len().max(1).next_multiple_of(16), 0);
+    bytes
+}
+
+/// Displays the parameter like `KEY_SIZE: f32 = 15.0 [10.0, 20.0] Size of the key`
+impl std::fmt::Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} = {}", self.name, self.ty, self.value)?;
+        if self.value != self.default {
+            write!(f, " (default {})", self.default)?;
+        }
+        if self.min.is_some() || self.max.is_some() {
+            let bound = |b: &Option<ParamValue>| b.map_or(String::new(), |b| b.to_string());
+            write!(f, " [{}, {}]", bound(&self.min), bound(&self.max))?;
+        }
+        if self.uniform {
+            write!(f, " (uniform)")?;
+        }
+        if !self.description.is_empty() {
+            write!(f, " {}", self.description)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Param::parse("const KEY_SIZE = 15.0;"), None);
+
+        let param = Param::parse("@param(min = 10, max = 20.0) const KEY_SIZE: f32 = 15; // Size")
+            .unwrap()
+            .unwrap();
+        assert_eq!(param.name, "KEY_SIZE");
+        assert_eq!(param.ty, ParamType::F32);
+        assert_eq!(param.value, ParamValue::F32(15.0));
+        assert_eq!(param.min, Some(ParamValue::F32(10.0)));
+        assert_eq!(param.max, Some(ParamValue::F32(20.0)));
+        assert_eq!(param.description, "Size");
+        assert_eq!(param.declaration(), "const KEY_SIZE: f32 = 15.0;");
+
+        let param = Param::parse("@param const STEPS = 4u;").unwrap().unwrap();
+        assert_eq!(param.ty, ParamType::U32);
+        assert_eq!(param.declaration(), "const STEPS: u32 = 4u;");
+
+        let param = Param::parse("@param const MASK = 0xff;").unwrap().unwrap();
+        assert_eq!(param.ty, ParamType::I32);
+        assert_eq!(param.value, ParamValue::I32(255));
+        let param = Param::parse("@param const MASK = 0xFFu;").unwrap().unwrap();
+        assert_eq!(param.value, ParamValue::U32(255));
+        let param = Param::parse("@param const OFFSET: i32 = -0x10;")
+            .unwrap()
+            .unwrap();
+        assert_eq!(param.value, ParamValue::I32(-16));
+
+        assert!(Param::parse("@param const A: vec3f = vec3f(1.0);")
+            .unwrap()
+            .is_err());
+        assert!(Param::parse("@param(max = 1) const A = 2;")
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn hex_floats() {
+        let param = Param::parse("@param const X = 0x1p4;").unwrap().unwrap();
+        assert_eq!(param.ty, ParamType::F32);
+        assert_eq!(param.value, ParamValue::F32(16.0));
+        assert_eq!(param.declaration(), "const X: f32 = 16.0;");
+
+        let param = Param::parse("@param const X = -0x1.8p-1f;")
+            .unwrap()
+            .unwrap();
+        assert_eq!(param.value, ParamValue::F32(-0.75));
+        let param = Param::parse("@param const X = 0x.f;").unwrap().unwrap();
+        assert_eq!(param.value, ParamValue::F32(0.9375));
+
+        let mut param = Param::parse("@param const X = 1.0;").unwrap().unwrap();
+        param.set_value("0x1p4").unwrap();
+        assert_eq!(param.value, ParamValue::F32(16.0));
+        assert!(param.set_value("0x.p1").is_err());
+        assert!(param.set_value("0x1g").is_err());
+    }
+
+    #[test]
+    fn set_value() {
+        let mut param = Param::parse("@param(min = 10.0, max = 20.0) const KEY_SIZE = 15.0;")
+            .unwrap()
+            .unwrap();
+        param.set_value("18").unwrap();
+        assert_eq!(param.declaration(), "const KEY_SIZE: f32 = 18.0;");
+        assert!(param.set_value("21").is_err());
+        assert!(param.set_value("large").is_err());
+
+        let mut param = Param::parse("@param const ENGRAVE = false;")
+            .unwrap()
+            .unwrap();
+        param.set_value("true").unwrap();
+        assert_eq!(param.value, ParamValue::Bool(true));
+        assert!(param.set_value("1").is_err());
+    }
+
+    #[test]
+    fn uniform_params() {
+        let params: Vec<Param> = [
+            "@param const A = 1.0;",
+            "@param(uniform, min = 0.0) const B = 2.0;",
+            "@param(uniform) const C: u32 = 3;",
+        ]
+        .iter()
+        .map(|line| Param::parse(line).unwrap().unwrap())
+        .collect();
+
+        assert_eq!(
+            uniform_params_wgsl(&params, 0, 7).unwrap(),
+            "struct Params {\n    B: f32,\n    C: u32,\n}\n\n@group(0)\n@binding(7)\nvar<uniform> params: Params;\n"
+        );
+        assert_eq!(uniform_params_wgsl(&params[..1], 0, 7), None);
+
+        let bytes = uniform_params_bytes(&params);
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(bytes[0..4], 2.0f32.to_le_bytes());
+        assert_eq!(bytes[4..8], 3u32.to_le_bytes());
+        assert_eq!(uniform_params_bytes(&params[..1]).len(), 16);
+
+        assert!(Param::parse("@param(uniform) const D = true;")
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,7 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU implementations of the built-in `sdf` shader modules
+
+pub mod noise;
+pub mod op;
@@ -0,0 +1,192 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU reference implementations of the noise functions in `sdf_noise.wgsl`.
+//! Each function matches the WGSL function with the prefix `sdf_noise_` and returns the same values for the same seed.
+
+use crate::{Scalar, Vec3D};
+
+/// Integer hash (lowbias32)
+fn hash(x: u32) -> u32 {
+    let mut h = x;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846ca68b);
+    h ^= h >> 16;
+    h
+}
+
+/// Pseudo-random gradient for the lattice point `c`, one of the 12 edge directions of a cube
+fn grad(c: [i32; 3], seed: u32) -> Vec3D {
+    let h = hash(c[0] as u32 ^ hash(c[1] as u32 ^ hash(c[2] as u32 ^ hash(seed))));
+    let s = |bit: u32| if h & bit != 0 { 1.0 } else { -1.0 };
+    let mut g = [s(1), s(2), s(4)];
+    g[((h >> 3) % 3) as usize] = 0.0;
+    g.into()
+}
+
+fn offset(c: [i32; 3], x: i32, y: i32, z: i32) -> [i32; 3] {
+    [c[0] + x, c[1] + y, c[2] + z]
+}
+
+fn mix(a: Scalar, b: Scalar, t: Scalar) -> Scalar {
+    a * (1.0 - t) + b * t
+}
+
+/// Gradient (Perlin) noise in the range [-1, 1]
+pub fn gradient(p: Vec3D, seed: u32) -> Scalar {
+    let i = p.floor();
+    let f = p - i;
+    let c = [i.x as i32, i.y as i32, i.z as i32];
+    let fade = |t: Scalar| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let u = Vec3D::new(fade(f.x), fade(f.y), fade(f.z));
+
+    let n = |x: i32, y: i32, z: i32| {
+        grad(offset(c, x, y, z), seed).dot(f - Vec3D::new(x as Scalar, y as Scalar, z as Scalar))
+    };
+    let x00 = mix(n(0, 0, 0), n(1, 0, 0), u.x);
+    let x10 = mix(n(0, 1, 0), n(1, 1, 0), u.x);
+    let x01 = mix(n(0, 0, 1), n(1, 0, 1), u.x);
+    let x11 = mix(n(0, 1, 1), n(1, 1, 1), u.x);
+    mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z)
+}
+
+/// Contribution of the simplex corner at `c` with the offset `x`
+fn simplex_corner(c: [i32; 3], x: Vec3D, seed: u32) -> Scalar {
+    let t = (0.6 - x.dot(x)).max(0.0);
+    t * t * t * t * grad(c, seed).dot(x)
+}
+
+/// Simplex noise in the range [-1, 1]
+pub fn simplex(p: Vec3D, seed: u32) -> Scalar {
+    let i = (p + Vec3D::splat((p.x + p.y + p.z) * (1.0 / 3.0))).floor();
+    let x0 = p - i + Vec3D::splat((i.x + i.y + i.z) * (1.0 / 6.0));
+
+    // Order of the axes from the largest to the smallest offset
+    let step = |edge: Scalar, x: Scalar| if x >= edge { 1.0 } else { 0.0 };
+    let g = Vec3D::new(step(x0.y, x0.x), step(x0.z, x0.y), step(x0.x, x0.z));
+    let l = Vec3D::splat(1.0) - g;
+    let i1 = g.min(Vec3D::new(l.z, l.x, l.y));
+    let i2 = g.max(Vec3D::new(l.z, l.x, l.y));
+
+    let x1 = x0 - i1 + Vec3D::splat(1.0 / 6.0);
+    let x2 = x0 - i2 + Vec3D::splat(2.0 / 6.0);
+    let x3 = x0 - Vec3D::splat(0.5);
+
+    let c = [i.x as i32, i.y as i32, i.z as i32];
+    let corner = |o: Vec3D| offset(c, o.x as i32, o.y as i32, o.z as i32);
+    32.0 * (simplex_corner(c, x0, seed)
+        + simplex_corner(corner(i1), x1, seed)
+        + simplex_corner(corner(i2), x2, seed)
+        + simplex_corner(offset(c, 1, 1, 1), x3, seed))
+}
+
+/// Sum of `octaves` octaves of simplex noise mapped by `noise`, normalized by the sum of the amplitudes
+fn octaves_sum(
+    p: Vec3D,
+    seed: u32,
+    octaves: i32,
+    lacunarity: Scalar,
+    gain: Scalar,
+    noise: impl Fn(Scalar) -> Scalar,
+) -> Scalar {
+    let mut sum = 0.0;
+    let mut norm = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    for i in 0..octaves.max(1) {
+        sum += amplitude * noise(simplex(p * frequency, seed.wrapping_add(i as u32)));
+        norm += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    sum / norm
+}
+
+/// Fractal Brownian motion of `octaves` simplex noise octaves in the range [-1, 1], at least one octave is used.
+/// Each octave multiplies the frequency by `lacunarity` and the amplitude by `gain`.
+pub fn fbm(p: Vec3D, seed: u32, octaves: i32, lacunarity: Scalar, gain: Scalar) -> Scalar {
+    octaves_sum(p, seed, octaves, lacunarity, gain, |n| n)
+}
+
+/// Ridged multifractal noise of `octaves` simplex noise octaves in the range [0, 1], with sharp ridges at 1
+pub fn ridged(p: Vec3D, seed: u32, octaves: i32, lacunarity: Scalar, gain: Scalar) -> Scalar {
+    octaves_sum(p, seed, octaves, lacunarity, gain, |n| {
+        let n = 1.0 - n.abs();
+        n * n
+    })
+}
+
+/// Warp the domain by simplex noise with the amplitude `strength`, pass the result to an SDF or another noise
+pub fn warp(p: Vec3D, seed: u32, strength: Scalar) -> Vec3D {
+    p + Vec3D::new(
+        simplex(p, seed),
+        simplex(p, seed.wrapping_add(1)),
+        simplex(p, seed.wrapping_add(2)),
+    ) * strength
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn ranges_and_seeds() {
+        let points = testing::sample_points(1024, 8.0);
+        for p in &points {
+            assert!(gradient(*p, 0).abs() <= 1.0);
+            assert!(simplex(*p, 0).abs() <= 1.0);
+            assert!((0.0..=1.0).contains(&ridged(*p, 0, 4, 2.0, 0.5)));
+            assert_eq!(simplex(*p, 7), simplex(*p, 7));
+
+            // Less than one octave is one octave
+            assert_eq!(fbm(*p, 0, 0, 2.0, 0.5), simplex(*p, 0));
+            assert_eq!(fbm(*p, 0, -1, 2.0, 0.5), simplex(*p, 0));
+            assert!(!ridged(*p, 0, 0, 2.0, 0.5).is_nan());
+        }
+
+        // Gradient noise vanishes on the lattice points
+        assert_eq!(gradient(Vec3D::new(3.0, -2.0, 5.0), 1), 0.0);
+
+        // Different seeds give different noise
+        let differs = |f: fn(Vec3D, u32) -> Scalar| points.iter().any(|p| f(*p, 1) != f(*p, 2));
+        assert!(differs(gradient));
+        assert!(differs(simplex));
+    }
+
+    /// Evaluate each noise function on the GPU and on the CPU and compare the results
+    #[test]
+    fn gpu_matches_cpu() {
+        type Noise = (&'static str, fn(Vec3D) -> Scalar);
+        let functions: &[Noise] = &[
+            ("sdf_noise_gradient(p, 0u)", |p| gradient(p, 0)),
+            ("sdf_noise_gradient(p, 12345u)", |p| gradient(p, 12345)),
+            ("sdf_noise_simplex(p, 0u)", |p| simplex(p, 0)),
+            ("sdf_noise_simplex(p, 4000000000u)", |p| {
+                simplex(p, 4000000000)
+            }),
+            ("sdf_noise_fbm(p, 3u, 4, 2.0, 0.5)", |p| {
+                fbm(p, 3, 4, 2.0, 0.5)
+            }),
+            ("sdf_noise_ridged(p, 3u, 3, 1.9, 0.6)", |p| {
+                ridged(p, 3, 3, 1.9, 0.6)
+            }),
+            ("sdf_noise_fbm(p, 3u, 0, 2.0, 0.5)", |p| {
+                fbm(p, 3, 0, 2.0, 0.5)
+            }),
+            ("sdf_noise_warp(p, 5u, 0.3).x", |p| warp(p, 5, 0.3).x),
+            ("sdf_noise_warp(p, 0xffffffffu, 0.3).z", |p| {
+                warp(p, u32::MAX, 0.3).z
+            }),
+        ];
+
+        testing::assert_gpu_matches(
+            include_str!("../sdf_noise.wgsl"),
+            functions,
+            &testing::sample_points(256, 6.0),
+            1e-4,
+        );
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU reference implementations of the operations in `sdf_op.wgsl`.
+//! Each function matches the WGSL function with the prefix `sdf_op_`.
+//! The subtractions remove the shape `d1` from the shape `d2`.
+
+use crate::{Scalar, Vec2D};
+
+/// GLSL `mod`, the result has the sign of `y`
+fn modulo(x: Scalar, y: Scalar) -> Scalar {
+    x - y * (x / y).floor()
+}
+
+fn mix(a: Scalar, b: Scalar, t: Scalar) -> Scalar {
+    a * (1.0 - t) + b * t
+}
+
+pub fn smooth_union(d1: Scalar, d2: Scalar, k: Scalar) -> Scalar {
+    let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+    mix(d2, d1, h) - k * h * (1.0 - h)
+}
+
+pub fn smooth_intersection(d1: Scalar, d2: Scalar, k: Scalar) -> Scalar {
+    let h = (0.5 - 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+    mix(d2, d1, h) + k * h * (1.0 - h)
+}
+
+pub fn smooth_subtraction(d1: Scalar, d2: Scalar, k: Scalar) -> Scalar {
+    let h = (0.5 - 0.5 * (d2 + d1) / k).clamp(0.0, 1.0);
+    mix(d2, -d1, h) + k * h * (1.0 - h)
+}
+
+pub fn union(d1: Scalar, d2: Scalar) -> Scalar {
+    d1.min(d2)
+}
+
+pub fn intersection(d1: Scalar, d2: Scalar) -> Scalar {
+    d1.max(d2)
+}
+
+pub fn subtraction(d1: Scalar, d2: Scalar) -> Scalar {
+    (-d1).max(d2)
+}
+
+/// Exponential smooth minimum with the blend size `k`
+pub fn smin_exp(d1: Scalar, d2: Scalar, k: Scalar) -> Scalar {
+    let r = (-d1 / k).exp2() + (-d2 / k).exp2();
+    -k * r.log2()
+}
+
+/// Square root smooth minimum with the blend size `k`
+pub fn smin_root(d1: Scalar, d2: Scalar, k: Scalar) -> Scalar {
+    let k2 = 2.0 * k;
+    let x = d2 - d1;
+    0.5 * (d1 + d2 - (x * x + k2 * k2).sqrt())
+}
+
+/// Quadratic polynomial smooth minimum with the blend size `k`
+pub fn smin_quadratic(d1: Scalar, d2: Scalar, k: Scalar) -> Scalar {
+    let k4 = 4.0 * k;
+    let h = (k4 - (d1 - d2).abs()).max(0.0) / k4;
+    d1.min(d2) - h * h * k4 * 0.25
+}
+
+/// Cubic polynomial smooth minimum with the blend size `k`
+pub fn smin_cubic(d1: Scalar, d2: Scalar, k: Scalar) -> Scalar {
+    let k6 = 6.0 * k;
+    let h = (k6 - (d1 - d2).abs()).max(0.0) / k6;
+    d1.min(d2) - h * h * h * k6 * (1.0 / 6.0)
+}
+
+/// Circular smooth minimum with the blend size `k`, which keeps the gradient length close to 1
+pub fn smin_circular(d1: Scalar, d2: Scalar, k: Scalar) -> Scalar {
+    let kc = k / (1.0 - 0.5f32.sqrt());
+    let h = (kc - (d1 - d2).abs()).max(0.0) / kc;
+    d1.min(d2) - kc * 0.5 * (1.0 + h - (1.0 - h * (h - 2.0)).sqrt())
+}
+
+/// Union rounded with the radius `r`, the distance is exact outside of the shapes
+pub fn round_union(d1: Scalar, d2: Scalar, r: Scalar) -> Scalar {
+    let u = Vec2D::new(r - d1, r - d2).max(Vec2D::zero());
+    r.max(d1.min(d2)) - u.length()
+}
+
+/// Intersection rounded with the radius `r`
+pub fn round_intersection(d1: Scalar, d2: Scalar, r: Scalar) -> Scalar {
+    let u = Vec2D::new(r + d1, r + d2).max(Vec2D::zero());
+    (-r).min(d1.max(d2)) + u.length()
+}
+
+/// Subtraction rounded with the radius `r`
+pub fn round_subtraction(d1: Scalar, d2: Scalar, r: Scalar) -> Scalar {
+    round_intersection(d2, -d1, r)
+}
+
+/// Union with a chamfer of the size `r`
+pub fn chamfer_union(d1: Scalar, d2: Scalar, r: Scalar) -> Scalar {
+    d1.min(d2).min((d1 - r + d2) * 0.5f32.sqrt())
+}
+
+/// Intersection with a chamfer of the size `r`
+pub fn chamfer_intersection(d1: Scalar, d2: Scalar, r: Scalar) -> Scalar {
+    d1.max(d2).max((d1 + r + d2) * 0.5f32.sqrt())
+}
+
+/// Subtraction with a chamfer of the size `r`
+pub fn chamfer_subtraction(d1: Scalar, d2: Scalar, r: Scalar) -> Scalar {
+    chamfer_intersection(d2, -d1, r)
+}
+
+/// Union blended with `n` stairs over the size `r`
+pub fn stairs_union(d1: Scalar, d2: Scalar, r: Scalar, n: Scalar) -> Scalar {
+    let s = r / n;
+    let u = d2 - r;
+    d1.min(d2)
+        .min(0.5 * (u + d1 + (modulo(u - d1 + s, 2.0 * s) - s).abs()))
+}
+
+/// Intersection blended with `n` stairs over the size `r`
+pub fn stairs_intersection(d1: Scalar, d2: Scalar, r: Scalar, n: Scalar) -> Scalar {
+    -stairs_union(-d1, -d2, r, n)
+}
+
+/// Subtraction blended with `n` stairs over the size `r`
+pub fn stairs_subtraction(d1: Scalar, d2: Scalar, r: Scalar, n: Scalar) -> Scalar {
+    -stairs_union(-d2, d1, r, n)
+}
+
+/// Union blended with `n` columns over the size `r`
+pub fn columns_union(d1: Scalar, d2: Scalar, r: Scalar, n: Scalar) -> Scalar {
+    if d1 >= r || d2 >= r {
+        return d1.min(d2);
+    }
+
+    let sqrt2 = 2.0f32.sqrt();
+    let column_radius = r * sqrt2 / ((n - 1.0) * 2.0 + sqrt2);
+    let mut p = (Vec2D::new(d1, d2) + Vec2D::new(d2, -d1)) * 0.5f32.sqrt();
+    p.x -= sqrt2 / 2.0 * r;
+    p.x += column_radius * sqrt2;
+    if modulo(n, 2.0) == 1.0 {
+        p.y += column_radius;
+    }
+    p.y = modulo(p.y + column_radius, column_radius * 2.0) - column_radius;
+    let result = (p.length() - column_radius).min(p.x).min(d1);
+    result.min(d2)
+}
+
+/// Remove `d2` from `d1` with `n` columns, like `fOpDifferenceColumns` of hg_sdf
+fn columns_difference(d1: Scalar, d2: Scalar, r: Scalar, n: Scalar) -> Scalar {
+    let a = -d1;
+    if a >= r || d2 >= r {
+        return -a.min(d2);
+    }
+
+    let sqrt2 = 2.0f32.sqrt();
+    let column_radius = r * sqrt2 / ((n - 1.0) * 2.0 + sqrt2);
+    let mut p = (Vec2D::new(a, d2) + Vec2D::new(d2, -a)) * 0.5f32.sqrt();
+    p.y += column_radius;
+    p.x -= sqrt2 / 2.0 * r;
+    p.x += -column_radius * sqrt2 / 2.0;
+    if modulo(n, 2.0) == 1.0 {
+        p.y += column_radius;
+    }
+    p.y = modulo(p.y + column_radius, column_radius * 2.0) - column_radius;
+    let result = (-p.length() + column_radius).max(p.x).min(a);
+    -result.min(d2)
+}
+
+/// Intersection blended with `n` columns over the size `r`
+pub fn columns_intersection(d1: Scalar, d2: Scalar, r: Scalar, n: Scalar) -> Scalar {
+    columns_difference(d1, -d2, r, n)
+}
+
+/// Subtraction blended with `n` columns over the size `r`
+pub fn columns_subtraction(d1: Scalar, d2: Scalar, r: Scalar, n: Scalar) -> Scalar {
+    columns_difference(d2, d1, r, n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf3d::primitives;
+    use crate::testing;
+    use crate::Vec3D;
+
+    #[test]
+    fn properties() {
+        assert_eq!(union(0.5, -0.2), -0.2);
+        assert_eq!(intersection(0.5, -0.2), 0.5);
+        assert_eq!(subtraction(-0.5, -0.2), 0.5);
+
+        // All smooth minimum variants stay below the hard minimum and approach it far from the blend
+        let smins: &[fn(Scalar, Scalar, Scalar) -> Scalar] = &[
+            smin_exp,
+            smin_root,
+            smin_quadratic,
+            smin_cubic,
+            smin_circular,
+        ];
+        for smin in smins {
+            assert!(smin(0.1, 0.12, 0.1) < 0.1);
+            assert!((smin(0.1, 2.0, 0.1) - 0.1).abs() < 1e-2);
+        }
+
+        // Outside of both shapes, the round union is the exact distance to the rounded corner
+        assert!((round_union(0.3, 0.4, 0.1) - 0.3).abs() < 1e-6);
+        let d = round_union(0.05, 0.05, 0.1);
+        assert!((d - (0.1 - 0.05 * 2.0f32.sqrt())).abs() < 1e-6);
+    }
+
+    /// Evaluate each operation on two primitives on the GPU and on the CPU and compare the results
+    #[test]
+    fn gpu_matches_cpu() {
+        type Operation = (&'static str, fn(Scalar, Scalar) -> Scalar);
+        let operations: &[Operation] = &[
+            ("sdf_op_smooth_union(A, B, 0.1)", |a, b| {
+                smooth_union(a, b, 0.1)
+            }),
+            ("sdf_op_smooth_intersection(A, B, 0.1)", |a, b| {
+                smooth_intersection(a, b, 0.1)
+            }),
+            ("sdf_op_smooth_subtraction(A, B, 0.1)", |a, b| {
+                smooth_subtraction(a, b, 0.1)
+            }),
+            ("sdf_op_union(A, B)", union),
+            ("sdf_op_intersection(A, B)", intersection),
+            ("sdf_op_subtraction(A, B)", subtraction),
+            ("sdf_op_smin_exp(A, B, 0.1)", |a, b| smin_exp(a, b, 0.1)),
+            ("sdf_op_smin_root(A, B, 0.1)", |a, b| smin_root(a, b, 0.1)),
+            ("sdf_op_smin_quadratic(A, B, 0.1)", |a, b| {
+                smin_quadratic(a, b, 0.1)
+            }),
+            ("sdf_op_smin_cubic(A, B, 0.1)", |a, b| smin_cubic(a, b, 0.1)),
+            ("sdf_op_smin_circular(A, B, 0.1)", |a, b| {
+                smin_circular(a, b, 0.1)
+            }),
+            ("sdf_op_round_union(A, B, 0.1)", |a, b| {
+                round_union(a, b, 0.1)
+            }),
+            ("sdf_op_round_intersection(A, B, 0.1)", |a, b| {
+                round_intersection(a, b, 0.1)
+            }),
+            ("sdf_op_round_subtraction(A, B, 0.1)", |a, b| {
+                round_subtraction(a, b, 0.1)
+            }),
+            ("sdf_op_chamfer_union(A, B, 0.1)", |a, b| {
+                chamfer_union(a, b, 0.1)
+            }),
+            ("sdf_op_chamfer_intersection(A, B, 0.1)", |a, b| {
+                chamfer_intersection(a, b, 0.1)
+            }),
+            ("sdf_op_chamfer_subtraction(A, B, 0.1)", |a, b| {
+                chamfer_subtraction(a, b, 0.1)
+            }),
+            ("sdf_op_stairs_union(A, B, 0.2, 4.0)", |a, b| {
+                stairs_union(a, b, 0.2, 4.0)
+            }),
+            ("sdf_op_stairs_intersection(A, B, 0.2, 4.0)", |a, b| {
+                stairs_intersection(a, b, 0.2, 4.0)
+            }),
+            ("sdf_op_stairs_subtraction(A, B, 0.2, 4.0)", |a, b| {
+                stairs_subtraction(a, b, 0.2, 4.0)
+            }),
+            ("sdf_op_columns_union(A, B, 0.2, 3.0)", |a, b| {
+                columns_union(a, b, 0.2, 3.0)
+            }),
+            ("sdf_op_columns_intersection(A, B, 0.2, 3.0)", |a, b| {
+                columns_intersection(a, b, 0.2, 3.0)
+            }),
+            ("sdf_op_columns_subtraction(A, B, 0.2, 4.0)", |a, b| {
+                columns_subtraction(a, b, 0.2, 4.0)
+            }),
+        ];
+
+        // `A` and `B` are the distances to a box and a sphere
+        let source = [
+            include_str!("../sdf3d_primitives.wgsl"),
+            include_str!("../sdf_op.wgsl"),
+            "const BOX = vec3(0.6, 0.4, 0.8);",
+        ]
+        .join("\n");
+        let functions: Vec<_> = operations
+            .iter()
+            .map(|(e, f)| {
+                let expression = e
+                    .replace("B", "sdf3d_sphere(p - vec3(0.3), 0.4)")
+                    .replace("A", "sdf3d_box(p, BOX)");
+                let function = move |p: Vec3D| {
+                    let a = primitives::box_(p, Vec3D::new(0.6, 0.4, 0.8));
+                    let b = primitives::sphere(p - Vec3D::splat(0.3), 0.4);
+                    f(a, b)
+                };
+                (expression, function)
+            })
+            .collect();
+        testing::assert_gpu_matches(&source, &functions, &testing::sample_points(256, 1.2), 1e-4);
+    }
+}
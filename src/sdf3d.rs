@@ -0,0 +1,9 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU implementations of the built-in `sdf3d` shader modules
+
+pub mod lattice;
+pub mod primitives;
+pub mod text;
+pub mod transform;
@@ -0,0 +1,225 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU reference implementations of the lattices and infills in `sdf3d_lattice.wgsl`.
+//! Each function matches the WGSL function with the prefix `sdf3d_`.
+
+use crate::{Scalar, Vec3D};
+use std::f32::consts::TAU;
+
+/// Approximate distance from the implicit function value `f` with the gradient `g` at the position scaled by `2π/cell`
+fn tpms_distance(f: Scalar, g: Vec3D, cell: Scalar) -> Scalar {
+    f / g.length().max(0.5) * cell / TAU
+}
+
+/// Sines and cosines of the position scaled by `2π/cell`
+fn sin_cos(p: Vec3D, cell: Scalar) -> (Vec3D, Vec3D) {
+    let q = p * TAU / cell;
+    (
+        Vec3D::new(q.x.sin(), q.y.sin(), q.z.sin()),
+        Vec3D::new(q.x.cos(), q.y.cos(), q.z.cos()),
+    )
+}
+
+fn yzx(v: Vec3D) -> Vec3D {
+    Vec3D::new(v.y, v.z, v.x)
+}
+
+fn zxy(v: Vec3D) -> Vec3D {
+    Vec3D::new(v.z, v.x, v.y)
+}
+
+/// Gyroid surface
+pub fn gyroid(p: Vec3D, cell: Scalar) -> Scalar {
+    let (s, c) = sin_cos(p, cell);
+    let f = s.dot(yzx(c));
+    let g = c.component_mul(yzx(c)) - zxy(s).component_mul(s);
+    tpms_distance(f, g, cell)
+}
+
+/// Schwarz Primitive (P) surface
+pub fn schwarz_p(p: Vec3D, cell: Scalar) -> Scalar {
+    let (s, c) = sin_cos(p, cell);
+    tpms_distance(c.x + c.y + c.z, -s, cell)
+}
+
+/// Schwarz Diamond (D) surface
+pub fn diamond(p: Vec3D, cell: Scalar) -> Scalar {
+    let (s, c) = sin_cos(p, cell);
+    let f = s.x * s.y * s.z + s.x * c.y * c.z + c.x * s.y * c.z + c.x * c.y * s.z;
+    let g = Vec3D::new(
+        c.x * s.y * s.z + c.x * c.y * c.z - s.x * s.y * c.z - s.x * c.y * s.z,
+        s.x * c.y * s.z - s.x * s.y * c.z + c.x * c.y * c.z - c.x * s.y * s.z,
+        s.x * s.y * c.z - s.x * c.y * s.z - c.x * s.y * s.z + c.x * c.y * c.z,
+    );
+    tpms_distance(f, g, cell)
+}
+
+/// Neovius surface
+pub fn neovius(p: Vec3D, cell: Scalar) -> Scalar {
+    let (s, c) = sin_cos(p, cell);
+    let f = 3.0 * (c.x + c.y + c.z) + 4.0 * c.x * c.y * c.z;
+    let g = s * -3.0 - s.component_mul(yzx(c)).component_mul(zxy(c)) * 4.0;
+    tpms_distance(f, g, cell)
+}
+
+/// Sheet with the wall `thickness` around the TPMS with the distance `d`
+pub fn tpms_sheet(d: Scalar, thickness: Scalar) -> Scalar {
+    d.abs() - 0.5 * thickness
+}
+
+/// Network solid on the negative side of the TPMS with the distance `d`, grown by `offset`
+pub fn tpms_network(d: Scalar, offset: Scalar) -> Scalar {
+    d - offset
+}
+
+pub fn gyroid_sheet(p: Vec3D, cell: Scalar, thickness: Scalar) -> Scalar {
+    tpms_sheet(gyroid(p, cell), thickness)
+}
+
+pub fn gyroid_network(p: Vec3D, cell: Scalar, offset: Scalar) -> Scalar {
+    tpms_network(gyroid(p, cell), offset)
+}
+
+pub fn schwarz_p_sheet(p: Vec3D, cell: Scalar, thickness: Scalar) -> Scalar {
+    tpms_sheet(schwarz_p(p, cell), thickness)
+}
+
+pub fn schwarz_p_network(p: Vec3D, cell: Scalar, offset: Scalar) -> Scalar {
+    tpms_network(schwarz_p(p, cell), offset)
+}
+
+pub fn diamond_sheet(p: Vec3D, cell: Scalar, thickness: Scalar) -> Scalar {
+    tpms_sheet(diamond(p, cell), thickness)
+}
+
+pub fn diamond_network(p: Vec3D, cell: Scalar, offset: Scalar) -> Scalar {
+    tpms_network(diamond(p, cell), offset)
+}
+
+pub fn neovius_sheet(p: Vec3D, cell: Scalar, thickness: Scalar) -> Scalar {
+    tpms_sheet(neovius(p, cell), thickness)
+}
+
+pub fn neovius_network(p: Vec3D, cell: Scalar, offset: Scalar) -> Scalar {
+    tpms_network(neovius(p, cell), offset)
+}
+
+/// Position in the octant [0, cell/2]³ of the unit cell, the lattices are symmetric to the cell planes
+fn lattice_cell(p: Vec3D, cell: Scalar) -> Vec3D {
+    let r = |p: Scalar| (p - cell * (p / cell).round_ties_even()).abs();
+    Vec3D::new(r(p.x), r(p.y), r(p.z))
+}
+
+/// Distance to the segment from `a` to `b`
+fn strut(p: Vec3D, a: Vec3D, b: Vec3D) -> Scalar {
+    let pa = p - a;
+    let ba = b - a;
+    let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+    (pa - ba * h).length()
+}
+
+/// Body-centered cubic beam lattice with the strut radius `r`
+pub fn lattice_bcc(p: Vec3D, cell: Scalar, r: Scalar) -> Scalar {
+    let q = lattice_cell(p, cell);
+    strut(q, Vec3D::zero(), Vec3D::splat(0.5 * cell)) - r
+}
+
+/// Octet truss beam lattice with the strut radius `r`, nodes are at the corners and face centers of the cells
+pub fn lattice_octet(p: Vec3D, cell: Scalar, r: Scalar) -> Scalar {
+    let q = lattice_cell(p, cell);
+    let h = 0.5 * cell;
+    let o = Vec3D::zero();
+    let a = Vec3D::new(h, h, 0.0);
+    let b = Vec3D::new(h, 0.0, h);
+    let c = Vec3D::new(0.0, h, h);
+    let d = strut(q, o, a)
+        .min(strut(q, o, b).min(strut(q, o, c)))
+        .min(strut(q, a, b).min(strut(q, b, c).min(strut(q, c, a))));
+    d - r
+}
+
+/// Shell with the wall `thickness` inside the solid with the distance `d`
+pub fn shell(d: Scalar, thickness: Scalar) -> Scalar {
+    d.max(-d - thickness)
+}
+
+/// Solid with the distance `d` as a shell with the wall `thickness`, filled with the `infill` distance
+pub fn shell_infill(d: Scalar, infill: Scalar, thickness: Scalar) -> Scalar {
+    shell(d, thickness).min(d.max(infill))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf3d::primitives;
+    use crate::testing;
+
+    #[test]
+    fn distances() {
+        // The Schwarz P surface passes through (cell/4, cell/4, cell/4) with the normal (-1, -1, -1)
+        let n = Vec3D::splat(-(1.0f32 / 3.0).sqrt());
+        let p = Vec3D::splat(0.5);
+        assert!(schwarz_p(p, 2.0).abs() < 1e-6);
+        assert!((schwarz_p(p + n * 0.02, 2.0) - 0.02).abs() < 1e-3);
+        assert!((schwarz_p_sheet(p + n * 0.02, 2.0, 0.1) + 0.03).abs() < 1e-3);
+
+        // The struts meet in the nodes
+        assert!((lattice_bcc(Vec3D::new(1.5, 0.5, -0.5), 2.0, 0.1) + 0.1).abs() < 1e-6);
+        assert!((lattice_octet(Vec3D::new(2.0, 1.0, -1.0), 2.0, 0.1) + 0.1).abs() < 1e-6);
+        assert!(lattice_octet(Vec3D::new(1.0, 1.0, 1.0), 2.0, 0.1) > 0.5);
+
+        // Shell with infill: the wall, the infill and the empty space between
+        assert_eq!(shell_infill(-0.02, 1.0, 0.1), -0.02);
+        assert_eq!(shell_infill(-0.5, -0.3, 0.1), -0.3);
+        assert_eq!(shell_infill(-0.5, 0.3, 0.1), 0.3);
+        assert_eq!(shell_infill(0.2, -0.3, 0.1), 0.2);
+    }
+
+    /// Evaluate each lattice on the GPU and on the CPU and compare the results
+    #[test]
+    fn gpu_matches_cpu() {
+        type Lattice = (&'static str, fn(Vec3D) -> Scalar);
+        let lattices: &[Lattice] = &[
+            ("sdf3d_gyroid(p, 0.7)", |p| gyroid(p, 0.7)),
+            ("sdf3d_schwarz_p(p, 0.7)", |p| schwarz_p(p, 0.7)),
+            ("sdf3d_diamond(p, 0.7)", |p| diamond(p, 0.7)),
+            ("sdf3d_neovius(p, 0.7)", |p| neovius(p, 0.7)),
+            ("sdf3d_gyroid_sheet(p, 0.5, 0.05)", |p| {
+                gyroid_sheet(p, 0.5, 0.05)
+            }),
+            ("sdf3d_schwarz_p_network(p, 0.5, 0.02)", |p| {
+                schwarz_p_network(p, 0.5, 0.02)
+            }),
+            ("sdf3d_diamond_sheet(p, 0.5, 0.05)", |p| {
+                diamond_sheet(p, 0.5, 0.05)
+            }),
+            ("sdf3d_neovius_network(p, 0.5, -0.02)", |p| {
+                neovius_network(p, 0.5, -0.02)
+            }),
+            ("sdf3d_lattice_bcc(p, 0.4, 0.03)", |p| {
+                lattice_bcc(p, 0.4, 0.03)
+            }),
+            ("sdf3d_lattice_octet(p, 0.4, 0.03)", |p| {
+                lattice_octet(p, 0.4, 0.03)
+            }),
+            (
+                "sdf3d_shell_infill(sdf3d_sphere(p, 1.0), sdf3d_lattice_octet(p, 0.3, 0.02), 0.05)",
+                |p| {
+                    shell_infill(
+                        primitives::sphere(p, 1.0),
+                        lattice_octet(p, 0.3, 0.02),
+                        0.05,
+                    )
+                },
+            ),
+        ];
+
+        let source = [
+            include_str!("../sdf3d_primitives.wgsl"),
+            include_str!("../sdf3d_lattice.wgsl"),
+        ]
+        .join("\n");
+        testing::assert_gpu_matches(&source, lattices, &testing::sample_points(256, 1.2), 1e-4);
+    }
+}
@@ -0,0 +1,339 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU reference implementations of the SDF primitives in `sdf3d_primitives.wgsl`.
+//! Each function matches the WGSL function with the prefix `sdf3d_`.
+
+use crate::{Scalar, Vec2D, Vec3D};
+
+/// Sign like in WGSL, zero for zero
+pub(crate) fn sign(x: Scalar) -> Scalar {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+fn max3(v: Vec3D) -> Scalar {
+    v.x.max(v.y.max(v.z))
+}
+
+pub fn box_(p: Vec3D, b: Vec3D) -> Scalar {
+    let q = p.abs() - b * 0.5;
+    q.max(Vec3D::zero()).length() + max3(q).min(0.0)
+}
+
+pub fn cylinder(p: Vec3D, h: Scalar, r: Scalar) -> Scalar {
+    let d = Vec2D::new(p.xz().length(), p.y).abs() - Vec2D::new(r, h);
+    d.x.max(d.y).min(0.0) + d.max(Vec2D::zero()).length()
+}
+
+pub fn capsule(p: Vec3D, a: Vec3D, b: Vec3D, r: Scalar) -> Scalar {
+    let pa = p - a;
+    let ba = b - a;
+    let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+    (pa - ba * h).length() - r
+}
+
+pub fn sphere(p: Vec3D, s: Scalar) -> Scalar {
+    p.length() - s
+}
+
+pub fn torus(p: Vec3D, t: Vec2D) -> Scalar {
+    let q = Vec2D::new(p.xz().length() - t.x, p.y);
+    q.length() - t.y
+}
+
+pub fn round_box(p: Vec3D, b: Vec3D, r: Scalar) -> Scalar {
+    let q = p.abs() - b * 0.5 + Vec3D::splat(r);
+    q.max(Vec3D::zero()).length() + max3(q).min(0.0) - r
+}
+
+pub fn box_frame(p: Vec3D, b: Vec3D, e: Scalar) -> Scalar {
+    let p1 = p.abs() - b * 0.5;
+    let q = (p1 + Vec3D::splat(e)).abs() - Vec3D::splat(e);
+    let edge = |v: Vec3D| v.max(Vec3D::zero()).length() + max3(v).min(0.0);
+    edge(Vec3D::new(p1.x, q.y, q.z))
+        .min(edge(Vec3D::new(q.x, p1.y, q.z)))
+        .min(edge(Vec3D::new(q.x, q.y, p1.z)))
+}
+
+/// Cone with the tip at the origin, `c` is the sine and cosine of the angle and `h` the height
+pub fn cone(p: Vec3D, c: Vec2D, h: Scalar) -> Scalar {
+    let q = Vec2D::new(c.x / c.y, -1.0) * h;
+    let w = Vec2D::new(p.xz().length(), p.y);
+    let a = w - q * (w.dot(q) / q.dot(q)).clamp(0.0, 1.0);
+    let b = w - q.component_mul(Vec2D::new((w.x / q.x).clamp(0.0, 1.0), 1.0));
+    let k = sign(q.y);
+    let d = a.dot(a).min(b.dot(b));
+    let s = (k * (w.x * q.y - w.y * q.x)).max(k * (w.y - q.y));
+    d.sqrt() * sign(s)
+}
+
+pub fn capped_cone(p: Vec3D, h: Scalar, r1: Scalar, r2: Scalar) -> Scalar {
+    let q = Vec2D::new(p.xz().length(), p.y);
+    let k1 = Vec2D::new(r2, h);
+    let k2 = Vec2D::new(r2 - r1, 2.0 * h);
+    let ca = Vec2D::new(
+        q.x - q.x.min(if q.y < 0.0 { r1 } else { r2 }),
+        q.y.abs() - h,
+    );
+    let cb = q - k1 + k2 * ((k1 - q).dot(k2) / k2.dot(k2)).clamp(0.0, 1.0);
+    let s = if cb.x < 0.0 && ca.y < 0.0 { -1.0 } else { 1.0 };
+    s * ca.dot(ca).min(cb.dot(cb)).sqrt()
+}
+
+pub fn round_cone(p: Vec3D, r1: Scalar, r2: Scalar, h: Scalar) -> Scalar {
+    let b = (r1 - r2) / h;
+    let a = (1.0 - b * b).sqrt();
+    let q = Vec2D::new(p.xz().length(), p.y);
+    let k = q.dot(Vec2D::new(-b, a));
+    if k < 0.0 {
+        return q.length() - r1;
+    }
+    if k > a * h {
+        return (q - Vec2D::new(0.0, h)).length() - r2;
+    }
+    q.dot(Vec2D::new(a, b)) - r1
+}
+
+/// Plane with normalized normal `n` and offset `h`
+pub fn plane(p: Vec3D, n: Vec3D, h: Scalar) -> Scalar {
+    p.dot(n) + h
+}
+
+pub fn hex_prism(p: Vec3D, h: Vec2D) -> Scalar {
+    let k = Vec3D::new(-0.8660254, 0.5, 0.57735);
+    let q = p.abs();
+    let xy = q.xy() - k.xy() * 2.0 * k.xy().dot(q.xy()).min(0.0);
+    let d = Vec2D::new(
+        (xy - Vec2D::new(xy.x.clamp(-k.z * h.x, k.z * h.x), h.x)).length() * sign(xy.y - h.x),
+        q.z - h.y,
+    );
+    d.x.max(d.y).min(0.0) + d.max(Vec2D::zero()).length()
+}
+
+pub fn tri_prism(p: Vec3D, h: Vec2D) -> Scalar {
+    let q = p.abs();
+    (q.z - h.y).max((q.x * 0.866025 + p.y * 0.5).max(-p.y) - h.x * 0.5)
+}
+
+pub fn ellipsoid(p: Vec3D, r: Vec3D) -> Scalar {
+    let k0 = p.component_div(r).length();
+    let k1 = p.component_div(r.component_mul(r)).length();
+    k0 * (k0 - 1.0) / k1
+}
+
+pub fn octahedron(p: Vec3D, s: Scalar) -> Scalar {
+    let a = p.abs();
+    let m = a.x + a.y + a.z - s;
+    let q = if 3.0 * a.x < m {
+        a
+    } else if 3.0 * a.y < m {
+        Vec3D::new(a.y, a.z, a.x)
+    } else if 3.0 * a.z < m {
+        Vec3D::new(a.z, a.x, a.y)
+    } else {
+        return m * 0.57735027;
+    };
+    let k = (0.5 * (q.z - q.y + s)).clamp(0.0, s);
+    Vec3D::new(q.x, q.y - s + k, q.z - k).length()
+}
+
+/// Pyramid with a unit square base at y = 0 and height `h`
+pub fn pyramid(p: Vec3D, h: Scalar) -> Scalar {
+    let m2 = h * h + 0.25;
+    let mut xz = p.xz().abs();
+    if xz.y > xz.x {
+        xz = xz.yx();
+    }
+    xz -= Vec2D::splat(0.5);
+    let q = Vec3D::new(xz.y, h * p.y - 0.5 * xz.x, h * xz.x + 0.5 * p.y);
+    let s = (-q.x).max(0.0);
+    let t = ((q.y - 0.5 * xz.y) / (m2 + 0.25)).clamp(0.0, 1.0);
+    let a = m2 * (q.x + s) * (q.x + s) + q.y * q.y;
+    let b = m2 * (q.x + 0.5 * t) * (q.x + 0.5 * t) + (q.y - m2 * t) * (q.y - m2 * t);
+    let d2 = if q.y.min(-q.x * m2 - q.y * 0.5) > 0.0 {
+        0.0
+    } else {
+        a.min(b)
+    };
+    ((d2 + q.z * q.z) / m2).sqrt() * sign(q.z.max(-p.y))
+}
+
+pub fn link(p: Vec3D, le: Scalar, r1: Scalar, r2: Scalar) -> Scalar {
+    let q = Vec3D::new(p.x, (p.y.abs() - le).max(0.0), p.z);
+    Vec2D::new(q.xy().length() - r1, q.z).length() - r2
+}
+
+/// Solid angle, `c` is the sine and cosine of the angle and `ra` the radius
+pub fn solid_angle(p: Vec3D, c: Vec2D, ra: Scalar) -> Scalar {
+    let q = Vec2D::new(p.xz().length(), p.y);
+    let l = q.length() - ra;
+    let m = (q - c * q.dot(c).clamp(0.0, ra)).length();
+    l.max(m * sign(c.y * q.x - c.x * q.y))
+}
+
+/// Capped torus, `sc` is the sine and cosine of the aperture
+pub fn capped_torus(p: Vec3D, sc: Vec2D, ra: Scalar, rb: Scalar) -> Scalar {
+    let q = Vec3D::new(p.x.abs(), p.y, p.z);
+    let k = if sc.y * q.x > sc.x * q.y {
+        q.xy().dot(sc)
+    } else {
+        q.xy().length()
+    };
+    (q.dot(q) + ra * ra - 2.0 * ra * k).sqrt() - rb
+}
+
+/// Cylinder between `a` and `b` with radius `r`
+pub fn cylinder_between(p: Vec3D, a: Vec3D, b: Vec3D, r: Scalar) -> Scalar {
+    let ba = b - a;
+    let pa = p - a;
+    let baba = ba.dot(ba);
+    let paba = pa.dot(ba);
+    let x = (pa * baba - ba * paba).length() - r * baba;
+    let y = (paba - baba * 0.5).abs() - baba * 0.5;
+    let x2 = x * x;
+    let y2 = y * y * baba;
+    let d = if x.max(y) < 0.0 {
+        -x2.min(y2)
+    } else {
+        (if x > 0.0 { x2 } else { 0.0 }) + (if y > 0.0 { y2 } else { 0.0 })
+    };
+    sign(d) * d.abs().sqrt() / baba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn distances() {
+        // Box of size 1.0 x 0.5 x 0.8: inside, on a face, outside of a face and of a corner
+        let b = Vec3D::new(1.0, 0.5, 0.8);
+        assert_eq!(box_(Vec3D::zero(), b), -0.25);
+        assert_eq!(box_(Vec3D::new(0.0, 0.25, 0.0), b), 0.0);
+        assert!((box_(Vec3D::new(0.7, 0.0, 0.0), b) - 0.2).abs() < 1e-6);
+        assert!((box_(Vec3D::new(0.8, 0.65, 0.4), b) - 0.5).abs() < 1e-6);
+        assert!((round_box(Vec3D::new(0.7, 0.0, 0.0), b, 0.1) - 0.2).abs() < 1e-6);
+
+        assert!((sphere(Vec3D::new(0.0, 0.3, 0.4), 0.7) + 0.2).abs() < 1e-6);
+        assert!((sphere(Vec3D::new(3.0, 4.0, 0.0), 0.7) - 4.3).abs() < 1e-6);
+
+        // Torus: the centre of the tube, the centre of the hole and above the tube
+        let t = Vec2D::new(0.5, 0.2);
+        assert!((torus(Vec3D::new(0.5, 0.0, 0.0), t) + 0.2).abs() < 1e-6);
+        assert!((torus(Vec3D::new(0.0, 0.0, -0.5), t) + 0.2).abs() < 1e-6);
+        assert!((torus(Vec3D::zero(), t) - 0.3).abs() < 1e-6);
+        assert!((torus(Vec3D::new(0.5, 0.5, 0.0), t) - 0.3).abs() < 1e-6);
+
+        assert!((cylinder(Vec3D::zero(), 0.6, 0.4) + 0.4).abs() < 1e-6);
+        assert!((cylinder(Vec3D::new(0.0, 1.0, 0.0), 0.6, 0.4) - 0.4).abs() < 1e-6);
+        assert!((cylinder(Vec3D::new(0.7, 0.0, 0.0), 0.6, 0.4) - 0.3).abs() < 1e-6);
+
+        // Capsule and cylinder between two points, beside and beyond the segment
+        let (a, c) = (Vec3D::zero(), Vec3D::new(0.0, 1.0, 0.0));
+        assert!((capsule(Vec3D::new(0.5, 0.5, 0.0), a, c, 0.2) - 0.3).abs() < 1e-6);
+        assert!((capsule(Vec3D::new(0.0, 1.5, 0.0), a, c, 0.2) - 0.3).abs() < 1e-6);
+        assert!((cylinder_between(Vec3D::new(0.5, 0.5, 0.0), a, c, 0.2) - 0.3).abs() < 1e-6);
+        assert!((cylinder_between(Vec3D::new(0.0, 1.5, 0.0), a, c, 0.2) - 0.5).abs() < 1e-6);
+
+        assert!((plane(Vec3D::new(3.0, 0.5, 1.0), c, 0.2) - 0.7).abs() < 1e-6);
+        assert!((ellipsoid(Vec3D::new(1.0, 0.0, 0.0), Vec3D::splat(0.5)) - 0.5).abs() < 1e-6);
+
+        // Octahedron: on a vertex and in the centre, at the distance to the faces
+        assert!(octahedron(Vec3D::new(0.6, 0.0, 0.0), 0.6).abs() < 1e-6);
+        assert!((octahedron(Vec3D::zero(), 0.6) + 0.6 / 3.0f32.sqrt()).abs() < 1e-6);
+    }
+
+    /// Evaluate each primitive on the GPU and on the CPU and compare the results
+    #[test]
+    fn gpu_matches_cpu() {
+        type Primitive = (&'static str, fn(Vec3D) -> Scalar);
+        let primitives: &[Primitive] = &[
+            ("sdf3d_box(p, vec3(1.0, 0.5, 0.8))", |p| {
+                box_(p, Vec3D::new(1.0, 0.5, 0.8))
+            }),
+            ("sdf3d_cylinder(p, 0.6, 0.4)", |p| cylinder(p, 0.6, 0.4)),
+            (
+                "sdf3d_capsule(p, vec3(-0.5, 0.0, 0.1), vec3(0.3, 0.5, 0.0), 0.2)",
+                |p| {
+                    capsule(
+                        p,
+                        Vec3D::new(-0.5, 0.0, 0.1),
+                        Vec3D::new(0.3, 0.5, 0.0),
+                        0.2,
+                    )
+                },
+            ),
+            ("sdf3d_sphere(p, 0.7)", |p| sphere(p, 0.7)),
+            ("sdf3d_torus(p, vec2(0.5, 0.2))", |p| {
+                torus(p, Vec2D::new(0.5, 0.2))
+            }),
+            ("sdf3d_round_box(p, vec3(1.0, 0.5, 0.8), 0.1)", |p| {
+                round_box(p, Vec3D::new(1.0, 0.5, 0.8), 0.1)
+            }),
+            ("sdf3d_box_frame(p, vec3(1.0, 0.5, 0.8), 0.05)", |p| {
+                box_frame(p, Vec3D::new(1.0, 0.5, 0.8), 0.05)
+            }),
+            ("sdf3d_cone(p, vec2(sin(0.5), cos(0.5)), 0.8)", |p| {
+                let (s, c) = 0.5f32.sin_cos();
+                cone(p, Vec2D::new(s, c), 0.8)
+            }),
+            ("sdf3d_capped_cone(p, 0.5, 0.4, 0.1)", |p| {
+                capped_cone(p, 0.5, 0.4, 0.1)
+            }),
+            ("sdf3d_round_cone(p, 0.3, 0.1, 0.6)", |p| {
+                round_cone(p, 0.3, 0.1, 0.6)
+            }),
+            ("sdf3d_plane(p, normalize(vec3(1.0, 2.0, 0.5)), 0.2)", |p| {
+                plane(p, Vec3D::new(1.0, 2.0, 0.5).normalize(), 0.2)
+            }),
+            ("sdf3d_hex_prism(p, vec2(0.4, 0.3))", |p| {
+                hex_prism(p, Vec2D::new(0.4, 0.3))
+            }),
+            ("sdf3d_tri_prism(p, vec2(0.6, 0.3))", |p| {
+                tri_prism(p, Vec2D::new(0.6, 0.3))
+            }),
+            ("sdf3d_ellipsoid(p, vec3(0.7, 0.4, 0.5))", |p| {
+                ellipsoid(p, Vec3D::new(0.7, 0.4, 0.5))
+            }),
+            ("sdf3d_octahedron(p, 0.6)", |p| octahedron(p, 0.6)),
+            ("sdf3d_pyramid(p, 0.8)", |p| pyramid(p, 0.8)),
+            ("sdf3d_link(p, 0.3, 0.3, 0.1)", |p| link(p, 0.3, 0.3, 0.1)),
+            ("sdf3d_solid_angle(p, vec2(sin(0.5), cos(0.5)), 0.8)", |p| {
+                let (s, c) = 0.5f32.sin_cos();
+                solid_angle(p, Vec2D::new(s, c), 0.8)
+            }),
+            (
+                "sdf3d_capped_torus(p, vec2(sin(0.5), cos(0.5)), 0.5, 0.1)",
+                |p| {
+                    let (s, c) = 0.5f32.sin_cos();
+                    capped_torus(p, Vec2D::new(s, c), 0.5, 0.1)
+                },
+            ),
+            (
+                "sdf3d_cylinder_between(p, vec3(-0.5, 0.0, 0.1), vec3(0.3, 0.5, 0.0), 0.2)",
+                |p| {
+                    cylinder_between(
+                        p,
+                        Vec3D::new(-0.5, 0.0, 0.1),
+                        Vec3D::new(0.3, 0.5, 0.0),
+                        0.2,
+                    )
+                },
+            ),
+        ];
+
+        testing::assert_gpu_matches(
+            include_str!("../sdf3d_primitives.wgsl"),
+            primitives,
+            &testing::sample_points(256, 1.2),
+            1e-4,
+        );
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU reference implementations of the text functions in `sdf3d_text.wgsl`.
+//! Each function matches the WGSL function with the prefix `sdf3d_`,
+//! the strokes of the text are passed as segments laid out by [`crate::text::layout`].
+
+use crate::{Scalar, Vec2D, Vec3D};
+
+/// Distance to the strokes `segments` of a text with the capital letter height 1
+pub fn text(p: Vec2D, segments: &[[f32; 4]]) -> Scalar {
+    segments.iter().fold(1e10, |d, [ax, ay, bx, by]| {
+        let pa = p - Vec2D::new(*ax, *ay);
+        let ba = Vec2D::new(bx - ax, by - ay);
+        let h = (pa.dot(ba) / ba.dot(ba).max(1e-12)).clamp(0.0, 1.0);
+        d.min((pa - ba * h).length())
+    })
+}
+
+/// Embossed text with the capital letter `height` and the `stroke` width, extruded from z = 0 to z = `depth`
+pub fn text_extrude(
+    p: Vec3D,
+    segments: &[[f32; 4]],
+    height: Scalar,
+    stroke: Scalar,
+    depth: Scalar,
+) -> Scalar {
+    let d = text(p.xy() / height, segments) * height - 0.5 * stroke;
+    let w = Vec2D::new(d, (p.z - 0.5 * depth).abs() - 0.5 * depth);
+    w.x.max(w.y).min(0.0) + w.max(Vec2D::zero()).length()
+}
+
+/// Engrave the text into the solid with the distance `d` from z = 0 down to z = -`depth`
+pub fn text_engrave(
+    d: Scalar,
+    p: Vec3D,
+    segments: &[[f32; 4]],
+    height: Scalar,
+    stroke: Scalar,
+    depth: Scalar,
+) -> Scalar {
+    d.max(-text_extrude(
+        p + Vec3D::new(0.0, 0.0, depth),
+        segments,
+        height,
+        stroke,
+        2.0 * depth,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf3d::primitives;
+    use crate::testing;
+    use crate::text::{layout, text_segments_wgsl};
+
+    #[test]
+    fn strokes() {
+        // `I` is centered at the origin with the vertical stroke through it
+        let segments = layout("I").unwrap();
+        assert_eq!(text(Vec2D::zero(), &segments), 0.0);
+        assert!((text(Vec2D::new(0.1, 0.0), &segments) - 0.1).abs() < 1e-6);
+
+        let p = Vec3D::new(0.0, 0.0, 0.05);
+        assert!((text_extrude(p, &segments, 1.0, 0.1, 0.1) + 0.05).abs() < 1e-6);
+        assert!(text_engrave(-0.5, -p, &segments, 1.0, 0.1, 0.1) > 0.0);
+        assert!(text_engrave(-0.5, Vec3D::new(0.3, 0.0, -0.05), &segments, 1.0, 0.1, 0.1) < 0.0);
+    }
+
+    /// Evaluate the text functions on the GPU and on the CPU and compare the results
+    #[test]
+    fn gpu_matches_cpu() {
+        let hello = layout("Hello, 42!").unwrap();
+        let sdf = layout("SDF").unwrap();
+        let source = [
+            include_str!("../sdf3d_primitives.wgsl"),
+            include_str!("../sdf3d_text.wgsl"),
+            &format!("const HELLO = vec2u(0u, {}u);", hello.len()),
+            &format!("const SDF = vec2u({}u, {}u);", hello.len(), sdf.len()),
+            &text_segments_wgsl(&[hello.clone(), sdf.clone()].concat()),
+        ]
+        .join("\n");
+
+        let functions: [(&str, &dyn Fn(Vec3D) -> Scalar); 3] = [
+            ("sdf3d_text(p.xy*3.0, HELLO)", &|p| text(p.xy() * 3.0, &hello)),
+            ("sdf3d_text_extrude(p, SDF, 0.4, 0.05, 0.1)", &|p| {
+                text_extrude(p, &sdf, 0.4, 0.05, 0.1)
+            }),
+            (
+                "sdf3d_text_engrave(sdf3d_box(p, vec3(1.5, 0.6, 0.4)), p - vec3(0.0, 0.0, 0.2), HELLO, 0.2, 0.03, 0.05)",
+                &|p| {
+                    text_engrave(
+                        primitives::box_(p, Vec3D::new(1.5, 0.6, 0.4)),
+                        p - Vec3D::new(0.0, 0.0, 0.2),
+                        &hello,
+                        0.2,
+                        0.03,
+                        0.05,
+                    )
+                },
+            ),
+        ];
+        testing::assert_gpu_matches(&source, &functions, &testing::sample_points(256, 1.0), 1e-4);
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! CPU reference implementations of the domain operations in `sdf3d_transform.wgsl`.
+//! Each function matches the WGSL function with the prefix `sdf3d_`.
+
+use crate::{Scalar, Vec3D};
+
+/// Move the SDF by `offset`
+pub fn translate(p: Vec3D, offset: Vec3D) -> Vec3D {
+    p - offset
+}
+
+/// Rotate the SDF by `angle` (radians) around `axis`
+pub fn rotate_axis_angle(p: Vec3D, axis: Vec3D, angle: Scalar) -> Vec3D {
+    let k = axis.normalize();
+    let c = angle.cos();
+    let s = -angle.sin();
+    p * c + k.cross(p) * s + k * k.dot(p) * (1.0 - c)
+}
+
+/// Rotate the SDF by the unit quaternion `q` (x, y, z, w)
+pub fn rotate_quat(p: Vec3D, q: [Scalar; 4]) -> Vec3D {
+    let u = -Vec3D::new(q[0], q[1], q[2]);
+    p + u.cross(u.cross(p) + p * q[3]) * 2.0
+}
+
+/// Rotate the SDF by the rotation matrix with the `columns`
+pub fn rotate_matrix(p: Vec3D, columns: [Vec3D; 3]) -> Vec3D {
+    Vec3D::new(columns[0].dot(p), columns[1].dot(p), columns[2].dot(p))
+}
+
+/// Scale the SDF uniformly by `s`, multiply the resulting distance by `s`
+pub fn scale(p: Vec3D, s: Scalar) -> Vec3D {
+    p / s
+}
+
+/// Mirror the half-space behind the plane through the origin with the unit normal `n` to the front
+pub fn mirror(p: Vec3D, n: Vec3D) -> Vec3D {
+    p - n * 2.0 * p.dot(n).min(0.0)
+}
+
+/// Make the SDF symmetric for each axis in `axes`
+pub fn symmetry(p: Vec3D, axes: [bool; 3]) -> Vec3D {
+    let s = |v: Scalar, axis: bool| if axis { v.abs() } else { v };
+    Vec3D::new(s(p.x, axes[0]), s(p.y, axes[1]), s(p.z, axes[2]))
+}
+
+/// Repeat the SDF infinitely with the spacing `s`
+pub fn repeat(p: Vec3D, s: Vec3D) -> Vec3D {
+    let r = |p: Scalar, s: Scalar| p - s * (p / s).round_ties_even();
+    Vec3D::new(r(p.x, s.x), r(p.y, s.y), r(p.z, s.z))
+}
+
+/// Repeat the SDF with the spacing `s`, `l` copies to each side of the origin
+pub fn repeat_limited(p: Vec3D, s: Vec3D, l: Vec3D) -> Vec3D {
+    let r = |p: Scalar, s: Scalar, l: Scalar| p - s * (p / s).round_ties_even().clamp(-l, l);
+    Vec3D::new(r(p.x, s.x, l.x), r(p.y, s.y, l.y), r(p.z, s.z, l.z))
+}
+
+/// Repeat the SDF `n` times around the Y axis
+pub fn repeat_polar(p: Vec3D, n: Scalar) -> Vec3D {
+    let sector = std::f32::consts::TAU / n;
+    let a = p.z.atan2(p.x) + 0.5 * sector;
+    let b = a - sector * (a / sector).floor() - 0.5 * sector;
+    let r = p.xz().length();
+    Vec3D::new(r * b.cos(), p.y, r * b.sin())
+}
+
+/// Twist the SDF around the Y axis by `k` radians per unit, the distance is not exact
+pub fn twist(p: Vec3D, k: Scalar) -> Vec3D {
+    let (s, c) = (k * p.y).sin_cos();
+    Vec3D::new(c * p.x - s * p.z, p.y, s * p.x + c * p.z)
+}
+
+/// Bend the SDF in the XY plane by `k` radians per unit, the distance is not exact
+pub fn bend(p: Vec3D, k: Scalar) -> Vec3D {
+    let (s, c) = (k * p.x).sin_cos();
+    Vec3D::new(c * p.x - s * p.y, s * p.x + c * p.y, p.z)
+}
+
+/// Elongate the SDF by `h` to each side.
+/// Evaluate the SDF at the returned position and add the returned offset to the distance.
+pub fn elongate(p: Vec3D, h: Vec3D) -> (Vec3D, Scalar) {
+    let q = p.abs() - h;
+    (q.max(Vec3D::zero()), q.x.max(q.y.max(q.z)).min(0.0))
+}
+
+/// Round the edges of the SDF with the distance `d` by the radius `r`
+pub fn round(d: Scalar, r: Scalar) -> Scalar {
+    d - r
+}
+
+/// Turn the SDF with the distance `d` into a shell with the thickness `2 * t`
+pub fn onion(d: Scalar, t: Scalar) -> Scalar {
+    d.abs() - t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf3d::primitives;
+    use crate::testing;
+    use crate::Vec2D;
+
+    #[test]
+    fn rotations_agree() {
+        let (s, c) = 0.7f32.sin_cos();
+        let p = Vec3D::new(0.3, -0.5, 0.8);
+        let a = rotate_axis_angle(p, Vec3D::new(0.0, 1.0, 0.0), 0.7);
+        let b = rotate_quat(p, [0.0, 0.35f32.sin(), 0.0, 0.35f32.cos()]);
+        let m = rotate_matrix(
+            p,
+            [
+                Vec3D::new(c, 0.0, -s),
+                Vec3D::new(0.0, 1.0, 0.0),
+                Vec3D::new(s, 0.0, c),
+            ],
+        );
+        assert!((a - b).length() < 1e-6);
+        assert!((a - m).length() < 1e-6);
+
+        // Rotating the SDF by +90° around Z moves the point on +X to +Y
+        let q = rotate_axis_angle(
+            Vec3D::new(0.0, 1.0, 0.0),
+            Vec3D::new(0.0, 0.0, 1.0),
+            std::f32::consts::FRAC_PI_2,
+        );
+        assert!((q - Vec3D::new(1.0, 0.0, 0.0)).length() < 1e-6);
+    }
+
+    /// Evaluate each operation applied to a primitive on the GPU and on the CPU and compare the results
+    #[test]
+    fn gpu_matches_cpu() {
+        type Operation = (&'static str, fn(Vec3D) -> Scalar);
+        fn box_(p: Vec3D) -> Scalar {
+            primitives::box_(p, Vec3D::new(0.3, 0.2, 0.4))
+        }
+        let operations: &[Operation] = &[
+            (
+                "sdf3d_box(sdf3d_translate(p, vec3(0.1, -0.2, 0.3)), B)",
+                |p| box_(translate(p, Vec3D::new(0.1, -0.2, 0.3))),
+            ),
+            (
+                "sdf3d_box(sdf3d_rotate_axis_angle(p, vec3(1.0, 2.0, 0.5), 0.7), B)",
+                |p| box_(rotate_axis_angle(p, Vec3D::new(1.0, 2.0, 0.5), 0.7)),
+            ),
+            (
+                "sdf3d_box(sdf3d_rotate_quat(p, vec4(0.0, sin(0.35), 0.0, cos(0.35))), B)",
+                |p| box_(rotate_quat(p, [0.0, 0.35f32.sin(), 0.0, 0.35f32.cos()])),
+            ),
+            (
+                "sdf3d_box(sdf3d_rotate_matrix(p, mat3x3f(cos(0.7), sin(0.7), 0.0, -sin(0.7), cos(0.7), 0.0, 0.0, 0.0, 1.0)), B)",
+                |p| {
+                    let (s, c) = 0.7f32.sin_cos();
+                    box_(rotate_matrix(
+                        p,
+                        [
+                            Vec3D::new(c, s, 0.0),
+                            Vec3D::new(-s, c, 0.0),
+                            Vec3D::new(0.0, 0.0, 1.0),
+                        ],
+                    ))
+                },
+            ),
+            ("sdf3d_box(sdf3d_scale(p, 1.5), B) * 1.5", |p| {
+                box_(scale(p, 1.5)) * 1.5
+            }),
+            (
+                "sdf3d_box(sdf3d_mirror(p - vec3(0.5, 0.0, 0.0), vec3(0.0, 1.0, 0.0)), B)",
+                |p| box_(mirror(p - Vec3D::new(0.5, 0.0, 0.0), Vec3D::new(0.0, 1.0, 0.0))),
+            ),
+            (
+                "sdf3d_box(sdf3d_symmetry(p, vec3(true, false, true)) - vec3(0.5, 0.1, 0.3), B)",
+                |p| box_(symmetry(p, [true, false, true]) - Vec3D::new(0.5, 0.1, 0.3)),
+            ),
+            ("sdf3d_box(sdf3d_repeat(p, vec3(0.7, 0.9, 1.1)), B)", |p| {
+                box_(repeat(p, Vec3D::new(0.7, 0.9, 1.1)))
+            }),
+            (
+                "sdf3d_box(sdf3d_repeat_limited(p, vec3(0.7), vec3(1.0, 0.0, 2.0)), B)",
+                |p| {
+                    box_(repeat_limited(
+                        p,
+                        Vec3D::splat(0.7),
+                        Vec3D::new(1.0, 0.0, 2.0),
+                    ))
+                },
+            ),
+            (
+                "sdf3d_box(sdf3d_repeat_polar(p, 6.0) - vec3(0.8, 0.0, 0.0), B)",
+                |p| box_(repeat_polar(p, 6.0) - Vec3D::new(0.8, 0.0, 0.0)),
+            ),
+            ("sdf3d_box(sdf3d_twist(p, 2.0), B)", |p| box_(twist(p, 2.0))),
+            ("sdf3d_box(sdf3d_bend(p, 1.5), B)", |p| box_(bend(p, 1.5))),
+            (
+                "sdf3d_torus(sdf3d_elongate(p, vec3(0.2, 0.0, 0.1)).xyz, vec2(0.5, 0.2)) + sdf3d_elongate(p, vec3(0.2, 0.0, 0.1)).w",
+                |p| {
+                    let (q, w) = elongate(p, Vec3D::new(0.2, 0.0, 0.1));
+                    primitives::torus(q, Vec2D::new(0.5, 0.2)) + w
+                },
+            ),
+            ("sdf3d_round(sdf3d_box(p, B), 0.1)", |p| round(box_(p), 0.1)),
+            ("sdf3d_onion(sdf3d_box(p, B), 0.05)", |p| onion(box_(p), 0.05)),
+        ];
+
+        let source = [
+            include_str!("../sdf3d_primitives.wgsl"),
+            include_str!("../sdf3d_transform.wgsl"),
+            "const B = vec3(0.3, 0.2, 0.4);",
+        ]
+        .join("\n");
+        testing::assert_gpu_matches(&source, operations, &testing::sample_points(256, 1.5), 1e-4);
+    }
+}
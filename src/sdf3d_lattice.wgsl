@@ -0,0 +1,161 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+// Lattices and infills.
+// Triply periodic minimal surfaces (TPMS) and beam lattices with the edge length `cell` of the cubic unit cell.
+// The TPMS distances are first-order estimates (implicit function divided by its gradient length),
+// they are accurate close to the surface.
+// Infills are combined with a solid by `sdf3d_shell_infill`, e.g.
+// `sdf3d_shell_infill(sdf3d_sphere(p, 1.0), sdf3d_gyroid_sheet(p, 0.2, 0.02), 0.05)`.
+
+/// Approximate distance from the implicit function value `f` with the gradient `g` at the position scaled by `2π/cell`
+fn _sdf3d_tpms_distance(f: f32, g: vec3f, cell: f32) -> f32
+{
+    return f/max(length(g), 0.5)*cell/6.283185307179586;
+}
+
+/// Gyroid surface
+fn sdf3d_gyroid(p: vec3f, cell: f32) -> f32
+{
+    let q = p*6.283185307179586/cell;
+    let s = sin(q);
+    let c = cos(q);
+    let f = dot(s, c.yzx);
+    let g = c*c.yzx - s.zxy*s;
+    return _sdf3d_tpms_distance(f, g, cell);
+}
+
+/// Schwarz Primitive (P) surface
+fn sdf3d_schwarz_p(p: vec3f, cell: f32) -> f32
+{
+    let q = p*6.283185307179586/cell;
+    let f = cos(q.x) + cos(q.y) + cos(q.z);
+    return _sdf3d_tpms_distance(f, -sin(q), cell);
+}
+
+/// Schwarz Diamond (D) surface
+fn sdf3d_diamond(p: vec3f, cell: f32) -> f32
+{
+    let q = p*6.283185307179586/cell;
+    let s = sin(q);
+    let c = cos(q);
+    let f = s.x*s.y*s.z + s.x*c.y*c.z + c.x*s.y*c.z + c.x*c.y*s.z;
+    let g = vec3(
+        c.x*s.y*s.z + c.x*c.y*c.z - s.x*s.y*c.z - s.x*c.y*s.z,
+        s.x*c.y*s.z - s.x*s.y*c.z + c.x*c.y*c.z - c.x*s.y*s.z,
+        s.x*s.y*c.z - s.x*c.y*s.z - c.x*s.y*s.z + c.x*c.y*c.z,
+    );
+    return _sdf3d_tpms_distance(f, g, cell);
+}
+
+/// Neovius surface
+fn sdf3d_neovius(p: vec3f, cell: f32) -> f32
+{
+    let q = p*6.283185307179586/cell;
+    let s = sin(q);
+    let c = cos(q);
+    let f = 3.0*(c.x + c.y + c.z) + 4.0*c.x*c.y*c.z;
+    let g = -3.0*s - 4.0*s*c.yzx*c.zxy;
+    return _sdf3d_tpms_distance(f, g, cell);
+}
+
+/// Sheet with the wall `thickness` around the TPMS with the distance `d`
+fn sdf3d_tpms_sheet(d: f32, thickness: f32) -> f32
+{
+    return abs(d) - 0.5*thickness;
+}
+
+/// Network solid on the negative side of the TPMS with the distance `d`, grown by `offset`
+fn sdf3d_tpms_network(d: f32, offset: f32) -> f32
+{
+    return d - offset;
+}
+
+fn sdf3d_gyroid_sheet(p: vec3f, cell: f32, thickness: f32) -> f32
+{
+    return sdf3d_tpms_sheet(sdf3d_gyroid(p, cell), thickness);
+}
+
+fn sdf3d_gyroid_network(p: vec3f, cell: f32, offset: f32) -> f32
+{
+    return sdf3d_tpms_network(sdf3d_gyroid(p, cell), offset);
+}
+
+fn sdf3d_schwarz_p_sheet(p: vec3f, cell: f32, thickness: f32) -> f32
+{
+    return sdf3d_tpms_sheet(sdf3d_schwarz_p(p, cell), thickness);
+}
+
+fn sdf3d_schwarz_p_network(p: vec3f, cell: f32, offset: f32) -> f32
+{
+    return sdf3d_tpms_network(sdf3d_schwarz_p(p, cell), offset);
+}
+
+fn sdf3d_diamond_sheet(p: vec3f, cell: f32, thickness: f32) -> f32
+{
+    return sdf3d_tpms_sheet(sdf3d_diamond(p, cell), thickness);
+}
+
+fn sdf3d_diamond_network(p: vec3f, cell: f32, offset: f32) -> f32
+{
+    return sdf3d_tpms_network(sdf3d_diamond(p, cell), offset);
+}
+
+fn sdf3d_neovius_sheet(p: vec3f, cell: f32, thickness: f32) -> f32
+{
+    return sdf3d_tpms_sheet(sdf3d_neovius(p, cell), thickness);
+}
+
+fn sdf3d_neovius_network(p: vec3f, cell: f32, offset: f32) -> f32
+{
+    return sdf3d_tpms_network(sdf3d_neovius(p, cell), offset);
+}
+
+/// Position in the octant [0, cell/2]³ of the unit cell, the lattices are symmetric to the cell planes
+fn _sdf3d_lattice_cell(p: vec3f, cell: f32) -> vec3f
+{
+    return abs(p - cell*round(p/cell));
+}
+
+/// Distance to the segment from `a` to `b`
+fn _sdf3d_lattice_strut(p: vec3f, a: vec3f, b: vec3f) -> f32
+{
+    let pa = p - a;
+    let ba = b - a;
+    let h = clamp(dot(pa, ba)/dot(ba, ba), 0.0, 1.0);
+    return length(pa - ba*h);
+}
+
+/// Body-centered cubic beam lattice with the strut radius `r`
+fn sdf3d_lattice_bcc(p: vec3f, cell: f32, r: f32) -> f32
+{
+    let q = _sdf3d_lattice_cell(p, cell);
+    return _sdf3d_lattice_strut(q, vec3f(), vec3(0.5*cell)) - r;
+}
+
+/// Octet truss beam lattice with the strut radius `r`, nodes are at the corners and face centers of the cells
+fn sdf3d_lattice_octet(p: vec3f, cell: f32, r: f32) -> f32
+{
+    let q = _sdf3d_lattice_cell(p, cell);
+    let h = 0.5*cell;
+    let a = vec3(h, h, 0.0);
+    let b = vec3(h, 0.0, h);
+    let c = vec3(0.0, h, h);
+    let d = min(
+        min(_sdf3d_lattice_strut(q, vec3f(), a), min(_sdf3d_lattice_strut(q, vec3f(), b), _sdf3d_lattice_strut(q, vec3f(), c))),
+        min(_sdf3d_lattice_strut(q, a, b), min(_sdf3d_lattice_strut(q, b, c), _sdf3d_lattice_strut(q, c, a))),
+    );
+    return d - r;
+}
+
+/// Shell with the wall `thickness` inside the solid with the distance `d`
+fn sdf3d_shell(d: f32, thickness: f32) -> f32
+{
+    return max(d, -d - thickness);
+}
+
+/// Solid with the distance `d` as a shell with the wall `thickness`, filled with the `infill` distance
+fn sdf3d_shell_infill(d: f32, infill: f32, thickness: f32) -> f32
+{
+    return min(sdf3d_shell(d, thickness), max(d, infill));
+}
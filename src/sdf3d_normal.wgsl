@@ -1,6 +1,7 @@
 // Copyright © Michael Winkelmann <michael@winkelmann.site>
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+/// Gradient of `sdf3d` at `p` by 4 samples at the corners of a tetrahedron with the size `eps`
 fn sdf3d_normal(p: vec3<f32>, eps: f32) -> vec3<f32> {
     let v1 = vec3( 1.0,-1.0,-1.0);
     let v2 = vec3(-1.0,-1.0, 1.0);
@@ -8,3 +9,13 @@ fn sdf3d_normal(p: vec3<f32>, eps: f32) -> vec3<f32> {
     let v4 = vec3( 1.0, 1.0, 1.0);
     return v1*sdf3d(p + v1*eps) + v2*sdf3d(p + v2*eps) + v3*sdf3d(p + v3*eps) + v4*sdf3d(p + v4*eps);
 }
+
+/// Gradient of `sdf3d` at `p` by 6 samples, central differences with the step `eps` along each axis
+fn sdf3d_normal_central(p: vec3<f32>, eps: f32) -> vec3<f32> {
+    let e = vec2(eps, 0.0);
+    return vec3(
+        sdf3d(p + e.xyy) - sdf3d(p - e.xyy),
+        sdf3d(p + e.yxy) - sdf3d(p - e.yxy),
+        sdf3d(p + e.yyx) - sdf3d(p - e.yyx),
+    );
+}
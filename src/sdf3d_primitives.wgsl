@@ -35,5 +35,168 @@ fn sdf3d_torus(p: vec3f, t: vec2f) -> f32
   return length(q)-t.y;
 }
 
+fn sdf3d_round_box(p: vec3f, b: vec3f, r: f32) -> f32
+{
+  let q = abs(p) - 0.5 * b + r;
+  return length(max(q,vec3f(0.0, 0.0, 0.0))) + min(max(q.x,max(q.y,q.z)),0.0) - r;
+}
+
+fn sdf3d_box_frame(p: vec3f, b: vec3f, e: f32) -> f32
+{
+  let p1 = abs(p) - 0.5 * b;
+  let q = abs(p1 + e) - e;
+  return min(min(
+      length(max(vec3(p1.x,q.y,q.z),vec3f())) + min(max(p1.x,max(q.y,q.z)),0.0),
+      length(max(vec3(q.x,p1.y,q.z),vec3f())) + min(max(q.x,max(p1.y,q.z)),0.0)),
+      length(max(vec3(q.x,q.y,p1.z),vec3f())) + min(max(q.x,max(q.y,p1.z)),0.0));
+}
+
+/// Cone with the tip at the origin, `c` is the sine and cosine of the angle and `h` the height
+fn sdf3d_cone(p: vec3f, c: vec2f, h: f32) -> f32
+{
+  let q = h * vec2(c.x / c.y, -1.0);
+  let w = vec2(length(p.xz), p.y);
+  let a = w - q * clamp(dot(w,q) / dot(q,q), 0.0, 1.0);
+  let b = w - q * vec2(clamp(w.x / q.x, 0.0, 1.0), 1.0);
+  let k = sign(q.y);
+  let d = min(dot(a,a), dot(b,b));
+  let s = max(k * (w.x*q.y - w.y*q.x), k * (w.y - q.y));
+  return sqrt(d) * sign(s);
+}
+
+fn sdf3d_capped_cone(p: vec3f, h: f32, r1: f32, r2: f32) -> f32
+{
+  let q = vec2(length(p.xz), p.y);
+  let k1 = vec2(r2, h);
+  let k2 = vec2(r2 - r1, 2.0*h);
+  let ca = vec2(q.x - min(q.x, select(r2, r1, q.y < 0.0)), abs(q.y) - h);
+  let cb = q - k1 + k2 * clamp(dot(k1 - q, k2) / dot(k2,k2), 0.0, 1.0);
+  let s = select(1.0, -1.0, cb.x < 0.0 && ca.y < 0.0);
+  return s * sqrt(min(dot(ca,ca), dot(cb,cb)));
+}
 
+fn sdf3d_round_cone(p: vec3f, r1: f32, r2: f32, h: f32) -> f32
+{
+  let b = (r1 - r2) / h;
+  let a = sqrt(1.0 - b*b);
+  let q = vec2(length(p.xz), p.y);
+  let k = dot(q, vec2(-b, a));
+  if k < 0.0 {
+    return length(q) - r1;
+  }
+  if k > a*h {
+    return length(q - vec2(0.0, h)) - r2;
+  }
+  return dot(q, vec2(a, b)) - r1;
+}
+
+/// Plane with normalized normal `n` and offset `h`
+fn sdf3d_plane(p: vec3f, n: vec3f, h: f32) -> f32
+{
+  return dot(p, n) + h;
+}
+
+fn sdf3d_hex_prism(p: vec3f, h: vec2f) -> f32
+{
+  let k = vec3(-0.8660254, 0.5, 0.57735);
+  var q = abs(p);
+  let xy = q.xy - 2.0 * min(dot(k.xy, q.xy), 0.0) * k.xy;
+  q = vec3(xy, q.z);
+  let d = vec2(
+    length(q.xy - vec2(clamp(q.x, -k.z*h.x, k.z*h.x), h.x)) * sign(q.y - h.x),
+    q.z - h.y);
+  return min(max(d.x,d.y),0.0) + length(max(d,vec2f()));
+}
+
+fn sdf3d_tri_prism(p: vec3f, h: vec2f) -> f32
+{
+  let q = abs(p);
+  return max(q.z - h.y, max(q.x*0.866025 + p.y*0.5, -p.y) - h.x*0.5);
+}
 
+fn sdf3d_ellipsoid(p: vec3f, r: vec3f) -> f32
+{
+  let k0 = length(p / r);
+  let k1 = length(p / (r*r));
+  return k0 * (k0 - 1.0) / k1;
+}
+
+fn sdf3d_octahedron(p: vec3f, s: f32) -> f32
+{
+  let a = abs(p);
+  let m = a.x + a.y + a.z - s;
+  var q: vec3f;
+  if 3.0*a.x < m {
+    q = a.xyz;
+  } else if 3.0*a.y < m {
+    q = a.yzx;
+  } else if 3.0*a.z < m {
+    q = a.zxy;
+  } else {
+    return m * 0.57735027;
+  }
+  let k = clamp(0.5 * (q.z - q.y + s), 0.0, s);
+  return length(vec3(q.x, q.y - s + k, q.z - k));
+}
+
+/// Pyramid with a unit square base at y = 0 and height `h`
+fn sdf3d_pyramid(p: vec3f, h: f32) -> f32
+{
+  let m2 = h*h + 0.25;
+  var xz = abs(p.xz);
+  if xz.y > xz.x {
+    xz = xz.yx;
+  }
+  xz -= 0.5;
+  let q = vec3(xz.y, h*p.y - 0.5*xz.x, h*xz.x + 0.5*p.y);
+  let s = max(-q.x, 0.0);
+  let t = clamp((q.y - 0.5*xz.y) / (m2 + 0.25), 0.0, 1.0);
+  let a = m2 * (q.x + s) * (q.x + s) + q.y*q.y;
+  let b = m2 * (q.x + 0.5*t) * (q.x + 0.5*t) + (q.y - m2*t) * (q.y - m2*t);
+  let d2 = select(min(a, b), 0.0, min(q.y, -q.x*m2 - q.y*0.5) > 0.0);
+  return sqrt((d2 + q.z*q.z) / m2) * sign(max(q.z, -p.y));
+}
+
+fn sdf3d_link(p: vec3f, le: f32, r1: f32, r2: f32) -> f32
+{
+  let q = vec3(p.x, max(abs(p.y) - le, 0.0), p.z);
+  return length(vec2(length(q.xy) - r1, q.z)) - r2;
+}
+
+/// Solid angle, `c` is the sine and cosine of the angle and `ra` the radius
+fn sdf3d_solid_angle(p: vec3f, c: vec2f, ra: f32) -> f32
+{
+  let q = vec2(length(p.xz), p.y);
+  let l = length(q) - ra;
+  let m = length(q - c * clamp(dot(q,c), 0.0, ra));
+  return max(l, m * sign(c.y*q.x - c.x*q.y));
+}
+
+/// Capped torus, `sc` is the sine and cosine of the aperture
+fn sdf3d_capped_torus(p: vec3f, sc: vec2f, ra: f32, rb: f32) -> f32
+{
+  let q = vec3(abs(p.x), p.yz);
+  var k = length(q.xy);
+  if sc.y*q.x > sc.x*q.y {
+    k = dot(q.xy, sc);
+  }
+  return sqrt(dot(q,q) + ra*ra - 2.0*ra*k) - rb;
+}
+
+/// Cylinder between `a` and `b` with radius `r`
+fn sdf3d_cylinder_between(p: vec3f, a: vec3f, b: vec3f, r: f32) -> f32
+{
+  let ba = b - a;
+  let pa = p - a;
+  let baba = dot(ba,ba);
+  let paba = dot(pa,ba);
+  let x = length(pa*baba - ba*paba) - r*baba;
+  let y = abs(paba - baba*0.5) - baba*0.5;
+  let x2 = x*x;
+  let y2 = y*y*baba;
+  var d = -min(x2, y2);
+  if max(x, y) >= 0.0 {
+    d = select(0.0, x2, x > 0.0) + select(0.0, y2, y > 0.0);
+  }
+  return sign(d) * sqrt(abs(d)) / baba;
+}
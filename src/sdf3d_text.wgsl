@@ -0,0 +1,35 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+// Text rendered with a bundled single-stroke font.
+// Texts are declared with `@text const LABEL = "SDF2MESH";`, the strokes of their glyphs are generated
+// into the array `SDF3D_TEXT_SEGMENTS` and `LABEL` is a handle with the first stroke and the number of strokes.
+// The text is centered at the origin of the XY plane, transform the position to place it on another plane.
+
+/// Distance to the strokes of the `text` with the capital letter height 1
+fn sdf3d_text(p: vec2f, text: vec2u) -> f32
+{
+    var d = 1e10;
+    for (var i = text.x; i < text.x + text.y; i++) {
+        let s = SDF3D_TEXT_SEGMENTS[i];
+        let pa = p - s.xy;
+        let ba = s.zw - s.xy;
+        let h = clamp(dot(pa, ba)/max(dot(ba, ba), 1e-12), 0.0, 1.0);
+        d = min(d, length(pa - ba*h));
+    }
+    return d;
+}
+
+/// Embossed `text` with the capital letter `height` and the `stroke` width, extruded from z = 0 to z = `depth`
+fn sdf3d_text_extrude(p: vec3f, text: vec2u, height: f32, stroke: f32, depth: f32) -> f32
+{
+    let d = sdf3d_text(p.xy/height, text)*height - 0.5*stroke;
+    let w = vec2(d, abs(p.z - 0.5*depth) - 0.5*depth);
+    return min(max(w.x, w.y), 0.0) + length(max(w, vec2f()));
+}
+
+/// Engrave the `text` into the solid with the distance `d` from z = 0 down to z = -`depth`
+fn sdf3d_text_engrave(d: f32, p: vec3f, text: vec2u, height: f32, stroke: f32, depth: f32) -> f32
+{
+    return max(d, -sdf3d_text_extrude(p + vec3(0.0, 0.0, depth), text, height, stroke, 2.0*depth));
+}
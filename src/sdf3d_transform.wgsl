@@ -0,0 +1,111 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+// Domain operations.
+// The functions transform the position before it is passed to an SDF, e.g.
+// `sdf3d_torus(sdf3d_translate(p, vec3(0.0, 0.5, 0.0)), vec2(0.5, 0.2))`.
+// mostly translated from https://iquilezles.org/articles/distfunctions/
+
+/// Move the SDF by `offset`
+fn sdf3d_translate(p: vec3f, offset: vec3f) -> vec3f
+{
+  return p - offset;
+}
+
+/// Rotate the SDF by `angle` (radians) around `axis`
+fn sdf3d_rotate_axis_angle(p: vec3f, axis: vec3f, angle: f32) -> vec3f
+{
+  let k = normalize(axis);
+  let c = cos(angle);
+  let s = -sin(angle);
+  return p*c + cross(k, p)*s + k*dot(k, p)*(1.0 - c);
+}
+
+/// Rotate the SDF by the unit quaternion `q` (x, y, z, w)
+fn sdf3d_rotate_quat(p: vec3f, q: vec4f) -> vec3f
+{
+  let u = -q.xyz;
+  return p + 2.0*cross(u, cross(u, p) + q.w*p);
+}
+
+/// Rotate the SDF by the rotation matrix `m`
+fn sdf3d_rotate_matrix(p: vec3f, m: mat3x3f) -> vec3f
+{
+  return transpose(m) * p;
+}
+
+/// Scale the SDF uniformly by `s`, multiply the resulting distance by `s`
+fn sdf3d_scale(p: vec3f, s: f32) -> vec3f
+{
+  return p / s;
+}
+
+/// Mirror the half-space behind the plane through the origin with the unit normal `n` to the front
+fn sdf3d_mirror(p: vec3f, n: vec3f) -> vec3f
+{
+  return p - 2.0*min(dot(p, n), 0.0)*n;
+}
+
+/// Make the SDF symmetric for each axis in `axes`
+fn sdf3d_symmetry(p: vec3f, axes: vec3<bool>) -> vec3f
+{
+  return select(p, abs(p), axes);
+}
+
+/// Repeat the SDF infinitely with the spacing `s`
+fn sdf3d_repeat(p: vec3f, s: vec3f) -> vec3f
+{
+  return p - s*round(p / s);
+}
+
+/// Repeat the SDF with the spacing `s`, `l` copies to each side of the origin
+fn sdf3d_repeat_limited(p: vec3f, s: vec3f, l: vec3f) -> vec3f
+{
+  return p - s*clamp(round(p / s), -l, l);
+}
+
+/// Repeat the SDF `n` times around the Y axis
+fn sdf3d_repeat_polar(p: vec3f, n: f32) -> vec3f
+{
+  let sector = 6.2831853 / n;
+  let a = atan2(p.z, p.x) + 0.5*sector;
+  let b = a - sector*floor(a / sector) - 0.5*sector;
+  let r = length(p.xz);
+  return vec3(r*cos(b), p.y, r*sin(b));
+}
+
+/// Twist the SDF around the Y axis by `k` radians per unit, the distance is not exact
+fn sdf3d_twist(p: vec3f, k: f32) -> vec3f
+{
+  let c = cos(k*p.y);
+  let s = sin(k*p.y);
+  return vec3(c*p.x - s*p.z, p.y, s*p.x + c*p.z);
+}
+
+/// Bend the SDF in the XY plane by `k` radians per unit, the distance is not exact
+fn sdf3d_bend(p: vec3f, k: f32) -> vec3f
+{
+  let c = cos(k*p.x);
+  let s = sin(k*p.x);
+  return vec3(c*p.x - s*p.y, s*p.x + c*p.y, p.z);
+}
+
+/// Elongate the SDF by `h` to each side.
+/// Evaluate the SDF at the returned `xyz` and add `w` to the distance.
+fn sdf3d_elongate(p: vec3f, h: vec3f) -> vec4f
+{
+  let q = abs(p) - h;
+  return vec4(max(q, vec3f()), min(max(q.x, max(q.y, q.z)), 0.0));
+}
+
+/// Round the edges of the SDF with the distance `d` by the radius `r`
+fn sdf3d_round(d: f32, r: f32) -> f32
+{
+  return d - r;
+}
+
+/// Turn the SDF with the distance `d` into a shell with the thickness `2 * t`
+fn sdf3d_onion(d: f32, t: f32) -> f32
+{
+  return abs(d) - t;
+}
@@ -0,0 +1,132 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+// Procedural noise.
+// All functions are deterministic and take a `seed`, different seeds give uncorrelated noise.
+// Adding noise to a distance, e.g. `sdf3d_sphere(p, 1.0) + 0.1*sdf_noise_fbm(p*4.0, 1u, 4, 2.0, 0.5)`,
+// breaks the distance property, the SDF might need to be scaled down to stay a lower bound.
+
+/// Integer hash (lowbias32)
+fn _sdf_noise_hash(x: u32) -> u32
+{
+    var h = x;
+    h ^= h >> 16u;
+    h *= 0x7feb352du;
+    h ^= h >> 15u;
+    h *= 0x846ca68bu;
+    h ^= h >> 16u;
+    return h;
+}
+
+/// Pseudo-random gradient for the lattice point `c`, one of the 12 edge directions of a cube
+fn _sdf_noise_grad(c: vec3i, seed: u32) -> vec3f
+{
+    let h = _sdf_noise_hash(bitcast<u32>(c.x) ^ _sdf_noise_hash(bitcast<u32>(c.y) ^ _sdf_noise_hash(bitcast<u32>(c.z) ^ _sdf_noise_hash(seed))));
+    var g = vec3(
+        select(-1.0, 1.0, (h & 1u) != 0u),
+        select(-1.0, 1.0, (h & 2u) != 0u),
+        select(-1.0, 1.0, (h & 4u) != 0u),
+    );
+    g[(h >> 3u) % 3u] = 0.0;
+    return g;
+}
+
+/// Gradient (Perlin) noise in the range [-1, 1]
+fn sdf_noise_gradient(p: vec3f, seed: u32) -> f32
+{
+    let i = floor(p);
+    let f = p - i;
+    let c = vec3i(i);
+    let u = f*f*f*(f*(f*6.0 - 15.0) + 10.0);
+
+    let n000 = dot(_sdf_noise_grad(c, seed), f);
+    let n100 = dot(_sdf_noise_grad(c + vec3(1, 0, 0), seed), f - vec3(1.0, 0.0, 0.0));
+    let n010 = dot(_sdf_noise_grad(c + vec3(0, 1, 0), seed), f - vec3(0.0, 1.0, 0.0));
+    let n110 = dot(_sdf_noise_grad(c + vec3(1, 1, 0), seed), f - vec3(1.0, 1.0, 0.0));
+    let n001 = dot(_sdf_noise_grad(c + vec3(0, 0, 1), seed), f - vec3(0.0, 0.0, 1.0));
+    let n101 = dot(_sdf_noise_grad(c + vec3(1, 0, 1), seed), f - vec3(1.0, 0.0, 1.0));
+    let n011 = dot(_sdf_noise_grad(c + vec3(0, 1, 1), seed), f - vec3(0.0, 1.0, 1.0));
+    let n111 = dot(_sdf_noise_grad(c + vec3(1, 1, 1), seed), f - vec3(1.0, 1.0, 1.0));
+
+    let x00 = mix(n000, n100, u.x);
+    let x10 = mix(n010, n110, u.x);
+    let x01 = mix(n001, n101, u.x);
+    let x11 = mix(n011, n111, u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+/// Contribution of the simplex corner at `c` with the offset `x`
+fn _sdf_noise_simplex_corner(c: vec3i, x: vec3f, seed: u32) -> f32
+{
+    let t = max(0.6 - dot(x, x), 0.0);
+    return t*t*t*t*dot(_sdf_noise_grad(c, seed), x);
+}
+
+/// Simplex noise in the range [-1, 1]
+fn sdf_noise_simplex(p: vec3f, seed: u32) -> f32
+{
+    let i = floor(p + (p.x + p.y + p.z)*(1.0/3.0));
+    let x0 = p - i + (i.x + i.y + i.z)*(1.0/6.0);
+
+    // Order of the axes from the largest to the smallest offset
+    let g = step(x0.yzx, x0.xyz);
+    let l = 1.0 - g;
+    let i1 = min(g, l.zxy);
+    let i2 = max(g, l.zxy);
+
+    let x1 = x0 - i1 + 1.0/6.0;
+    let x2 = x0 - i2 + 2.0/6.0;
+    let x3 = x0 - 0.5;
+
+    let c = vec3i(i);
+    return 32.0*(
+        _sdf_noise_simplex_corner(c, x0, seed) +
+        _sdf_noise_simplex_corner(c + vec3i(i1), x1, seed) +
+        _sdf_noise_simplex_corner(c + vec3i(i2), x2, seed) +
+        _sdf_noise_simplex_corner(c + vec3(1, 1, 1), x3, seed)
+    );
+}
+
+/// Fractal Brownian motion of `octaves` simplex noise octaves in the range [-1, 1], at least one octave is used.
+/// Each octave multiplies the frequency by `lacunarity` and the amplitude by `gain`.
+fn sdf_noise_fbm(p: vec3f, seed: u32, octaves: i32, lacunarity: f32, gain: f32) -> f32
+{
+    var sum = 0.0;
+    var norm = 0.0;
+    var amplitude = 1.0;
+    var frequency = 1.0;
+    for (var i = 0; i < max(octaves, 1); i++) {
+        sum += amplitude*sdf_noise_simplex(p*frequency, seed + u32(i));
+        norm += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    return sum/norm;
+}
+
+/// Ridged multifractal noise of `octaves` simplex noise octaves in the range [0, 1], with sharp ridges at 1
+fn sdf_noise_ridged(p: vec3f, seed: u32, octaves: i32, lacunarity: f32, gain: f32) -> f32
+{
+    var sum = 0.0;
+    var norm = 0.0;
+    var amplitude = 1.0;
+    var frequency = 1.0;
+    for (var i = 0; i < max(octaves, 1); i++) {
+        let n = 1.0 - abs(sdf_noise_simplex(p*frequency, seed + u32(i)));
+        sum += amplitude*n*n;
+        norm += amplitude;
+        amplitude *= gain;
+        frequency *= lacunarity;
+    }
+    return sum/norm;
+}
+
+/// Warp the domain by simplex noise with the amplitude `strength`, pass the result to an SDF or another noise
+fn sdf_noise_warp(p: vec3f, seed: u32, strength: f32) -> vec3f
+{
+    return p + strength*vec3(
+        sdf_noise_simplex(p, seed),
+        sdf_noise_simplex(p, seed + 1u),
+        sdf_noise_simplex(p, seed + 2u),
+    );
+}
@@ -21,3 +21,168 @@ fn sdf_op_smooth_subtraction(d1: f32, d2: f32, k: f32) -> f32
     let h = clamp(0.5 - 0.5*(d2+d1)/k, 0.0, 1.0);
     return mix(d2, -d1, h) + k*h*(1.0-h);
 }
+
+// Hard boolean operations.
+// `sdf_op_subtraction(d1, d2)` removes the shape `d1` from the shape `d2`, like the smooth version.
+
+fn sdf_op_union(d1: f32, d2: f32) -> f32
+{
+    return min(d1, d2);
+}
+
+fn sdf_op_intersection(d1: f32, d2: f32) -> f32
+{
+    return max(d1, d2);
+}
+
+fn sdf_op_subtraction(d1: f32, d2: f32) -> f32
+{
+    return max(-d1, d2);
+}
+
+// Smooth minimum variants, translated from https://iquilezles.org/articles/smin/
+// `k` is the size of the blend region in distance units for all variants.
+// A smooth maximum is `-sdf_op_smin_*(-d1, -d2, k)`.
+
+fn sdf_op_smin_exp(d1: f32, d2: f32, k: f32) -> f32
+{
+    let r = exp2(-d1/k) + exp2(-d2/k);
+    return -k*log2(r);
+}
+
+fn sdf_op_smin_root(d1: f32, d2: f32, k: f32) -> f32
+{
+    let k2 = 2.0*k;
+    let x = d2 - d1;
+    return 0.5*(d1 + d2 - sqrt(x*x + k2*k2));
+}
+
+fn sdf_op_smin_quadratic(d1: f32, d2: f32, k: f32) -> f32
+{
+    let k4 = 4.0*k;
+    let h = max(k4 - abs(d1 - d2), 0.0)/k4;
+    return min(d1, d2) - h*h*k4*0.25;
+}
+
+fn sdf_op_smin_cubic(d1: f32, d2: f32, k: f32) -> f32
+{
+    let k6 = 6.0*k;
+    let h = max(k6 - abs(d1 - d2), 0.0)/k6;
+    return min(d1, d2) - h*h*h*k6*(1.0/6.0);
+}
+
+/// Blends with a circular arc, which keeps the gradient length close to 1
+fn sdf_op_smin_circular(d1: f32, d2: f32, k: f32) -> f32
+{
+    let kc = k/(1.0 - sqrt(0.5));
+    let h = max(kc - abs(d1 - d2), 0.0)/kc;
+    return min(d1, d2) - kc*0.5*(1.0 + h - sqrt(1.0 - h*(h - 2.0)));
+}
+
+// Blends with a radius `r`, translated from the hg_sdf library https://mercury.sexy/hg_sdf/
+// The round variants keep the distance property outside of the shapes.
+
+fn _sdf_op_mod(x: f32, y: f32) -> f32
+{
+    return x - y*floor(x/y);
+}
+
+fn sdf_op_round_union(d1: f32, d2: f32, r: f32) -> f32
+{
+    let u = max(vec2(r - d1, r - d2), vec2f());
+    return max(r, min(d1, d2)) - length(u);
+}
+
+fn sdf_op_round_intersection(d1: f32, d2: f32, r: f32) -> f32
+{
+    let u = max(vec2(r + d1, r + d2), vec2f());
+    return min(-r, max(d1, d2)) + length(u);
+}
+
+fn sdf_op_round_subtraction(d1: f32, d2: f32, r: f32) -> f32
+{
+    return sdf_op_round_intersection(d2, -d1, r);
+}
+
+fn sdf_op_chamfer_union(d1: f32, d2: f32, r: f32) -> f32
+{
+    return min(min(d1, d2), (d1 - r + d2)*sqrt(0.5));
+}
+
+fn sdf_op_chamfer_intersection(d1: f32, d2: f32, r: f32) -> f32
+{
+    return max(max(d1, d2), (d1 + r + d2)*sqrt(0.5));
+}
+
+fn sdf_op_chamfer_subtraction(d1: f32, d2: f32, r: f32) -> f32
+{
+    return sdf_op_chamfer_intersection(d2, -d1, r);
+}
+
+/// Blends with `n` stairs
+fn sdf_op_stairs_union(d1: f32, d2: f32, r: f32, n: f32) -> f32
+{
+    let s = r/n;
+    let u = d2 - r;
+    return min(min(d1, d2), 0.5*(u + d1 + abs(_sdf_op_mod(u - d1 + s, 2.0*s) - s)));
+}
+
+fn sdf_op_stairs_intersection(d1: f32, d2: f32, r: f32, n: f32) -> f32
+{
+    return -sdf_op_stairs_union(-d1, -d2, r, n);
+}
+
+fn sdf_op_stairs_subtraction(d1: f32, d2: f32, r: f32, n: f32) -> f32
+{
+    return -sdf_op_stairs_union(-d2, d1, r, n);
+}
+
+/// Blends with `n` columns
+fn sdf_op_columns_union(d1: f32, d2: f32, r: f32, n: f32) -> f32
+{
+    if d1 >= r || d2 >= r {
+        return min(d1, d2);
+    }
+
+    let column_radius = r*sqrt(2.0)/((n - 1.0)*2.0 + sqrt(2.0));
+    var p = (vec2(d1, d2) + vec2(d2, -d1))*sqrt(0.5);
+    p.x -= sqrt(2.0)/2.0*r;
+    p.x += column_radius*sqrt(2.0);
+    if _sdf_op_mod(n, 2.0) == 1.0 {
+        p.y += column_radius;
+    }
+    p.y = _sdf_op_mod(p.y + column_radius, column_radius*2.0) - column_radius;
+    let result = min(min(length(p) - column_radius, p.x), d1);
+    return min(result, d2);
+}
+
+/// Removes `d2` from `d1` with `n` columns, like `fOpDifferenceColumns` of hg_sdf
+fn _sdf_op_columns_difference(d1: f32, d2: f32, r: f32, n: f32) -> f32
+{
+    let a = -d1;
+    if a >= r || d2 >= r {
+        return -min(a, d2);
+    }
+
+    let column_radius = r*sqrt(2.0)/((n - 1.0)*2.0 + sqrt(2.0));
+    var p = (vec2(a, d2) + vec2(d2, -a))*sqrt(0.5);
+    p.y += column_radius;
+    p.x -= sqrt(2.0)/2.0*r;
+    p.x += -column_radius*sqrt(2.0)/2.0;
+    if _sdf_op_mod(n, 2.0) == 1.0 {
+        p.y += column_radius;
+    }
+    p.y = _sdf_op_mod(p.y + column_radius, column_radius*2.0) - column_radius;
+    let result = min(max(-length(p) + column_radius, p.x), a);
+    return -min(result, d2);
+}
+
+fn sdf_op_columns_intersection(d1: f32, d2: f32, r: f32, n: f32) -> f32
+{
+    return _sdf_op_columns_difference(d1, -d2, r, n);
+}
+
+fn sdf_op_columns_subtraction(d1: f32, d2: f32, r: f32, n: f32) -> f32
+{
+    return _sdf_op_columns_difference(d2, d1, r, n);
+}
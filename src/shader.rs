@@ -3,18 +3,27 @@
 
 use common_macros::hash_map;
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Lines, Read, Write};
+use std::path::{Path, PathBuf};
 
+use crate::channel::{Channel, CHANNEL_COUNT};
+use crate::param::{self, Param};
 use crate::shadertoy;
+use crate::source_map::{SourceMap, SourceOrigin};
+use crate::text::{self, Text};
 
 lazy_static! {
     static ref BUILTIN_MODULES: std::collections::HashMap<&'static str, &'static str> = {
         hash_map! {
+            "sdf::noise" => include_str!("sdf_noise.wgsl"),
             "sdf::op" => include_str!("sdf_op.wgsl"),
+            "sdf3d::lattice" => include_str!("sdf3d_lattice.wgsl"),
             "sdf3d::normal" => include_str!("sdf3d_normal.wgsl"),
             "sdf3d::primitives" => include_str!("sdf3d_primitives.wgsl"),
+            "sdf3d::text" => include_str!("sdf3d_text.wgsl"),
+            "sdf3d::transform" => include_str!("sdf3d_transform.wgsl"),
         }
     };
 }
@@ -30,120 +39,627 @@ pub fn read_lines<P: AsRef<std::path::Path>>(
 
 type ModuleHandlers = HashMap<String, Box<dyn Fn(&mut dyn Write) -> std::io::Result<()>>>;
 
-/// Our shader
+/// Hook to read files from a virtual filesystem, returns `None` for files it does not provide
+type FileLoader = Box<dyn Fn(&Path) -> Option<String>>;
+
+/// Errors while reading the source of a shader
+#[derive(Debug)]
+pub enum ShaderSourceError {
+    /// A file could not be read
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// A `use` statement refers to a module that does not exist
+    UnknownModule {
+        file: PathBuf,
+        line: usize,
+        module: String,
+    },
+    /// A `use` statement has a module name with an empty, `.` or `..` segment or a path separator
+    InvalidModule {
+        file: PathBuf,
+        line: usize,
+        module: String,
+    },
+    /// An `include` statement refers to a file that was not found
+    IncludeNotFound {
+        file: PathBuf,
+        line: usize,
+        include: PathBuf,
+    },
+    /// A file includes itself, directly or via other files
+    IncludeCycle {
+        file: PathBuf,
+        line: usize,
+        include: PathBuf,
+    },
+    /// A `@param` declaration is malformed or declared twice
+    InvalidParam {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// A `@text` declaration is malformed, declared twice or has characters the font does not have
+    InvalidText {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// The value of a parameter override has the wrong type or is out of range
+    InvalidDefine { name: String, message: String },
+    /// A parameter override refers to a parameter that is not declared
+    UnknownDefine { name: String },
+    /// Analytic normals are requested, but `sdf3d_gradient` is not defined
+    MissingGradient,
+    /// `sdf3d_gradient` is defined with another signature than `fn sdf3d_gradient(p: vec3f) -> vec3f`
+    InvalidGradient { signature: String },
+}
+
+impl std::fmt::Display for ShaderSourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "Could not read {}: {error}", path.display()),
+            Self::UnknownModule { file, line, module } => {
+                write!(f, "{}:{line}: Unknown module `{module}`", file.display())
+            }
+            Self::InvalidModule { file, line, module } => {
+                write!(f, "{}:{line}: Invalid module name `{module}`", file.display())
+            }
+            Self::IncludeNotFound {
+                file,
+                line,
+                include,
+            } => write!(
+                f,
+                "{}:{line}: Included file {} not found",
+                file.display(),
+                include.display()
+            ),
+            Self::IncludeCycle {
+                file,
+                line,
+                include,
+            } => write!(
+                f,
+                "{}:{line}: Cyclic include of {}",
+                file.display(),
+                include.display()
+            ),
+            Self::InvalidParam {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{line}: Invalid parameter: {message}", file.display()),
+            Self::InvalidText {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{line}: Invalid text: {message}", file.display()),
+            Self::InvalidDefine { name, message } => {
+                write!(f, "Invalid value for parameter `{name}`: {message}")
+            }
+            Self::UnknownDefine { name } => write!(f, "Unknown parameter `{name}`"),
+            Self::MissingGradient => write!(
+                f,
+                "Analytic normals require a function `{SDF3D_GRADIENT_SIGNATURE}`"
+            ),
+            Self::InvalidGradient { signature } => write!(
+                f,
+                "`sdf3d_gradient` has the signature `{signature}`, expected `{SDF3D_GRADIENT_SIGNATURE}`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderSourceError {}
+
+/// A location in the original source that is labeled by a compiler error
+#[derive(Debug)]
+pub struct ShaderErrorLabel {
+    /// Origin of the line, `None` if the line could not be mapped
+    pub origin: Option<SourceOrigin>,
+    /// Line number in the origin, starting at 1
+    pub line: usize,
+    /// Column, starting at 1
+    pub column: usize,
+    /// Description of the label
+    pub label: String,
+    /// Text of the labeled line
+    pub source_line: String,
+}
+
+/// Error while compiling the composed shader source, mapped back to the original sources
+#[derive(Debug)]
+pub struct ShaderCompileError {
+    /// Error message
+    pub message: String,
+    /// Labeled locations in the original sources
+    pub labels: Vec<ShaderErrorLabel>,
+}
+
+impl std::fmt::Display for ShaderCompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Shader error: {}", self.message)?;
+        for label in &self.labels {
+            let origin = label
+                .origin
+                .as_ref()
+                .map_or("<unknown>".to_string(), ToString::to_string);
+            write!(
+                f,
+                "\n  {origin}:{}:{}: {}\n    {}",
+                label.line,
+                label.column,
+                label.label,
+                label.source_line.trim()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderCompileError {}
+
+/// Environment variable with directories to search for module files, separated like `PATH`
+pub const MODULE_PATH_ENV: &str = "SDF2MESH_MODULE_PATH";
+
+/// Bind group of the uniform struct `params` with the parameters declared with `@param(uniform)`
+pub const PARAMS_GROUP: u32 = 0;
+
+/// Binding of the uniform struct `params`, next to `app_state`
+pub const PARAMS_BINDING: u32 = 7;
+
+/// How the normals of the mesh vertices are estimated
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NormalEstimation {
+    /// Analytic normals if the shader defines `sdf3d_gradient`, tetrahedral otherwise
+    #[default]
+    Auto,
+    /// Gradient from 4 samples at the corners of a tetrahedron
+    Tetrahedral,
+    /// Gradient from 6 samples, central differences along each axis
+    Central,
+    /// Gradient from the function `fn sdf3d_gradient(p: vec3f) -> vec3f` defined in the shader
+    Analytic,
+}
+
+/// Signature of the SDF function every shader must define
+const SDF3D_SIGNATURE: &str = "fn sdf3d(p: vec3f) -> f32";
+
+/// Signature of the gradient function used for analytic normals
+const SDF3D_GRADIENT_SIGNATURE: &str = "fn sdf3d_gradient(p: vec3f) -> vec3f";
+
+const VEC3F: naga::TypeInner = naga::TypeInner::Vector {
+    size: naga::VectorSize::Tri,
+    scalar: naga::Scalar::F32,
+};
+
+/// The function takes a single `vec3<f32>` and returns a value of type `result`
+fn takes_vec3f(module: &naga::Module, function: &naga::Function, result: &naga::TypeInner) -> bool {
+    let is = |ty: naga::Handle<naga::Type>, expected: &naga::TypeInner| {
+        module.types[ty].inner == *expected
+    };
+
+    matches!(function.arguments.as_slice(), [argument] if is(argument.ty, &VEC3F))
+        && function
+            .result
+            .as_ref()
+            .is_some_and(|result_type| is(result_type.ty, result))
+}
+
+/// The function takes a single `vec3<f32>` and returns an `f32`, like the SDF function
+fn is_sdf_function(module: &naga::Module, function: &naga::Function) -> bool {
+    takes_vec3f(
+        module,
+        function,
+        &naga::TypeInner::Scalar(naga::Scalar::F32),
+    )
+}
+
+/// The function of `module` named `name`
+fn find_function<'a>(
+    module: &'a naga::Module,
+    name: &str,
+) -> Option<(naga::Handle<naga::Function>, &'a naga::Function)> {
+    module
+        .functions
+        .iter()
+        .find(|(_, function)| function.name.as_deref() == Some(name))
+}
+
+/// Signature of a function in WGSL without argument names, e.g. `fn sdf3d(vec3<f32>) -> f32`
+fn function_signature(module: &naga::Module, function: &naga::Function) -> String {
+    let gctx = module.to_ctx();
+    let arguments: Vec<_> = function
+        .arguments
+        .iter()
+        .map(|argument| argument.ty.to_wgsl(&gctx))
+        .collect();
+    let result = function.result.as_ref().map_or(String::new(), |result| {
+        format!(" -> {}", result.ty.to_wgsl(&gctx))
+    });
+    format!(
+        "fn {}({}){result}",
+        function.name.as_deref().unwrap_or_default(),
+        arguments.join(", ")
+    )
+}
+
+/// Names of the functions of `module` with the signature of an SDF function, `float(vec3)`
+pub fn sdf_function_candidates(module: &naga::Module) -> Vec<String> {
+    module
+        .functions
+        .iter()
+        .filter(|(_, function)| is_sdf_function(module, function))
+        .filter_map(|(_, function)| function.name.clone())
+        .collect()
+}
+
+/// File extension of module files
+const MODULE_FILE_EXTENSION: &str = "wgsl";
+
+/// Source of a module imported by `use`
+enum ModuleSource {
+    /// Module registered with `add_module`
+    Registered,
+    /// Module file found in a module path
+    File(PathBuf),
+}
+
+/// State while reading the source of a shader with its includes
 #[derive(Default)]
+struct SourceReader {
+    /// Files that are currently being read, to detect include cycles
+    include_stack: Vec<PathBuf>,
+    /// Files that have already been included
+    included_files: HashSet<PathBuf>,
+    /// Modules that have already been imported
+    imported_modules: HashSet<String>,
+    /// Parameters declared so far
+    params: Vec<Param>,
+    /// Texts declared so far
+    texts: Vec<Text>,
+    /// Strokes of the texts declared so far
+    text_segments: Vec<[f32; 4]>,
+}
+
+impl SourceReader {
+    /// A parameter or text with `name` has been declared
+    fn is_declared(&self, name: &str) -> bool {
+        self.params.iter().any(|param| param.name == name)
+            || self.texts.iter().any(|text| text.name == name)
+    }
+}
+
+/// Our shader
 pub struct Sdf3DShader {
     /// Source code of the shader
     source: String,
+    /// Origin of each line of the source code
+    source_map: SourceMap,
     /// Handlers to import modules
     modules: ModuleHandlers,
+    /// Directories to search for included files
+    include_paths: Vec<PathBuf>,
+    /// Directories to search for module files
+    module_paths: Vec<PathBuf>,
+    /// Virtual filesystem which is asked for included files and module files before the disk
+    file_loader: Option<FileLoader>,
+    /// Values overriding the defaults of parameters
+    defines: HashMap<String, String>,
+    /// Parameters declared in the source
+    params: Vec<Param>,
+    /// Texts declared in the source
+    texts: Vec<Text>,
+    /// Textures of the channels of a ShaderToy shader, bound in the group `channel::CHANNEL_GROUP`
+    channels: [Option<Channel>; CHANNEL_COUNT],
+}
+
+impl Default for Sdf3DShader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Sdf3DShader {
-    /// Construct a new `Sdf3DShader` from a path
-    pub fn from_path(path: impl AsRef<std::path::Path>) -> Self {
+    /// Construct a new `Sdf3DShader` with the built-in modules and without source
+    pub fn new() -> Self {
         let mut s = Self {
             source: String::new(),
-            modules: HashMap::new(),
+            source_map: SourceMap::default(),
+            modules: ModuleHandlers::new(),
+            include_paths: Vec::new(),
+            module_paths: Vec::new(),
+            file_loader: None,
+            defines: HashMap::new(),
+            params: Vec::new(),
+            texts: Vec::new(),
+            channels: Default::default(),
         };
 
-        s.add_module("sdf::*", |w| write!(w, "{}", BUILTIN_MODULES["sdf::op"]))
-            .add_module("sdf::op", |w| write!(w, "{}", BUILTIN_MODULES["sdf::op"]))
-            .add_module("sdf3d::normal", |w| {
-                write!(w, "{}", BUILTIN_MODULES["sdf3d::normal"])
-            })
-            .add_module("sdf3d::primitives", |w| {
-                write!(w, "{}", BUILTIN_MODULES["sdf3d::primitives"])
-            })
-            .add_module("sdf3d::*", |w| {
-                write!(w, "{}", BUILTIN_MODULES["sdf3d::primitives"])?;
-                write!(w, "{}", BUILTIN_MODULES["sdf3d::normal"])?;
-                Ok(())
-            });
+        for (name, source) in BUILTIN_MODULES.iter() {
+            s.add_module(name, move |w| write!(w, "{}", source));
+        }
+
+        s
+    }
 
-        s.source = s.shader_source(&path);
+    /// Construct a new `Sdf3DShader` from a path
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ShaderSourceError> {
+        let mut s = Self::new();
+        s.load_file(path)?;
+        Ok(s)
+    }
+
+    /// Construct a new `Sdf3DShader` from WGSL source code in memory.
+    /// Included files are searched relative to the current directory and in the include paths.
+    pub fn from_wgsl_str(source: &str) -> Result<Self, ShaderSourceError> {
+        let mut s = Self::new();
+        s.load_str("<string>", source)?;
+        Ok(s)
+    }
+
+    /// Construct a new `Sdf3DShader` from WGSL source code read from `reader`
+    pub fn from_reader(reader: impl Read) -> Result<Self, ShaderSourceError> {
+        let mut s = Self::new();
+        s.load_reader("<reader>", reader)?;
+        Ok(s)
+    }
+
+    /// Add a directory to search for included files.
+    /// Included files are first searched relative to the including file, then in the include paths.
+    pub fn add_include_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.include_paths.push(path.into());
+        self
+    }
+
+    /// Add a directory to search for module files.
+    /// `use acme::gears;` imports the file `acme/gears.wgsl` of the first module path that contains it.
+    pub fn add_module_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.module_paths.push(path.into());
+        self
+    }
+
+    /// Add the module paths given in the environment variable `SDF2MESH_MODULE_PATH`
+    pub fn add_module_paths_from_env(&mut self) -> &mut Self {
+        if let Some(paths) = std::env::var_os(MODULE_PATH_ENV) {
+            self.module_paths.extend(std::env::split_paths(&paths));
+        }
+        self
+    }
 
+    /// Set a hook to read included files and module files from a virtual filesystem.
+    /// The hook is asked for each candidate path before the disk and returns `None` if it does not provide the file.
+    pub fn set_file_loader(
+        &mut self,
+        loader: impl Fn(&Path) -> Option<String> + 'static,
+    ) -> &mut Self {
+        self.file_loader = Some(Box::new(loader));
+        self
+    }
+
+    /// Override the default value of the parameter or text `name` when the source is loaded.
+    /// The value is checked against the type and range of the parameter, texts are checked against the font.
+    pub fn define(&mut self, name: &str, value: &str) -> &mut Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Parameters declared with `@param` in the loaded source, with their current values
+    pub fn params(&self) -> &[Param] {
+        &self.params
+    }
+
+    /// Texts declared with `@text` in the loaded source, with their current values
+    pub fn texts(&self) -> &[Text] {
+        &self.texts
+    }
+
+    /// Textures of the channels `iChannel0` to `iChannel3`, read by shaders from ShaderToy
+    pub fn channels(&self) -> &[Option<Channel>; CHANNEL_COUNT] {
+        &self.channels
+    }
+
+    /// Register a module with its WGSL source, e.g. `acme::gears`.
+    /// Registered modules take precedence over module files.
+    pub fn add_module_source(&mut self, name: &str, source: impl Into<String>) -> &mut Self {
+        let source = source.into();
+        self.add_module(name, move |w| writeln!(w, "{}", source))
+    }
+
+    /// Read the shader source from a file, resolving its modules and includes
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), ShaderSourceError> {
+        let path = path.as_ref();
+        let text = self.read_file(path)?;
+        self.load_str(path, &text)
+    }
+
+    /// Read the shader source from a string, resolving its modules and includes.
+    /// `path` is used to resolve relative includes and in error messages, it does not need to exist.
+    pub fn load_str(
+        &mut self,
+        path: impl AsRef<Path>,
+        text: &str,
+    ) -> Result<(), ShaderSourceError> {
+        let mut source = String::new();
+        let mut source_map = SourceMap::default();
+        let mut reader = SourceReader::default();
+        self.shader_source_input(
+            path.as_ref(),
+            text,
+            &mut reader,
+            &mut source,
+            &mut source_map,
+        )?;
+
+        let mut defines: Vec<_> = self.defines.keys().collect();
+        defines.sort();
+        if let Some(name) = defines.into_iter().find(|name| !reader.is_declared(name)) {
+            return Err(ShaderSourceError::UnknownDefine { name: name.clone() });
+        }
+
+        if let Some(wgsl) = param::uniform_params_wgsl(&reader.params, PARAMS_GROUP, PARAMS_BINDING)
+        {
+            source_map.append(&mut source, &wgsl, &SourceOrigin::Named("<params>".into()));
+        }
+
+        if reader.imported_modules.contains("sdf3d::text") {
+            let wgsl = text::text_segments_wgsl(&reader.text_segments);
+            source_map.append(&mut source, &wgsl, &SourceOrigin::Named("<text>".into()));
+        }
+
+        self.source = source;
+        self.source_map = source_map;
+        self.params = reader.params;
+        self.texts = reader.texts;
+        Ok(())
+    }
+
+    /// Read the shader source from `reader`, resolving its modules and includes.
+    /// `path` is used to resolve relative includes and in error messages, it does not need to exist.
+    pub fn load_reader(
+        &mut self,
+        path: impl AsRef<Path>,
+        mut reader: impl Read,
+    ) -> Result<(), ShaderSourceError> {
+        let path = path.as_ref();
+        let mut text = String::new();
+        reader
+            .read_to_string(&mut text)
+            .map_err(|error| ShaderSourceError::Io {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        self.load_str(path, &text)
+    }
+
+    /// Construct a new `Sdf3DShader` from generated source code, e.g. converted GLSL
+    fn from_generated_source(name: &str, source: &str) -> Self {
+        let mut s = Self::default();
+        s.add_to_source(name, source);
         s
     }
 
     /// Construct a new `Sdf3DShader` from glsl shader
     ///
     /// * `path`: Path of the GLSL
-    /// * `sdf`: Function name of the SDF function, e.g. `float sdf(vec3)`
+    /// * `sdf`: Function name of the SDF function, e.g. `float sdf(vec3)`, detected if `None`
     pub fn from_glsl_fragment_shader(
         path: impl AsRef<std::path::Path>,
-        sdf: &str,
+        sdf: Option<&str>,
+    ) -> Result<Self, shadertoy::ShaderProcessingError> {
+        let glsl = std::fs::read_to_string(&path)?;
+        Self::from_glsl(&path.as_ref().display().to_string(), &glsl, sdf)
+    }
+
+    /// Construct a new `Sdf3DShader` from glsl shader source code in memory
+    ///
+    /// * `glsl`: Source code of the GLSL fragment shader
+    /// * `sdf`: Function name of the SDF function, e.g. `float sdf(vec3)`, detected if `None`
+    pub fn from_glsl_str(
+        glsl: &str,
+        sdf: Option<&str>,
+    ) -> Result<Self, shadertoy::ShaderProcessingError> {
+        Self::from_glsl("<string>", glsl, sdf)
+    }
+
+    /// Construct a new `Sdf3DShader` from glsl shader, `name` is used in error messages
+    fn from_glsl(
+        name: &str,
+        glsl: &str,
+        sdf: Option<&str>,
     ) -> Result<Self, shadertoy::ShaderProcessingError> {
         use shadertoy::*;
-        let mut file = File::open(path).unwrap();
 
-        let mut glsl = String::new();
-        file.read_to_string(&mut glsl).unwrap();
+        let mut wgsl = WgslShaderCode::from_glsl(glsl)?;
+        let sdf = wgsl.find_sdf_function(sdf)?;
+        log::info!("SDF function: {sdf}");
 
-        let mut wgsl = WgslShaderCode::from_glsl(&glsl)?;
-        wgsl.remove_function("fn main_1(")?;
-        wgsl.remove_function("fn main(")?;
-        wgsl.remove_line("@fragment"); // Remove fragment entry point
+        wgsl.remove_entry_points();
+        wgsl.remove_function("main")?;
         wgsl.add_line(include_str!("sdf3d_normal.wgsl"));
 
-        if wgsl.has_function(sdf) {
-            if !wgsl.has_function("sdf3d") {
-                // Generate function wrapper
-                wgsl.add_line(
-                    format!("fn sdf3d(p: vec3<f32>) -> f32 {{ return {}(p); }}", sdf).as_str(),
-                );
-            }
-        } else {
-            return Err(shadertoy::ShaderProcessingError::MissingSdf(sdf.into()));
+        // Generate function wrapper, the WGSL writer renames functions like `sdf2` to `sdf2_`
+        let sdf = wgsl.wgsl_function_name(&sdf)?;
+        if sdf != "sdf3d" {
+            wgsl.add_line(
+                format!("fn sdf3d(p: vec3<f32>) -> f32 {{ return {}(p); }}", sdf).as_str(),
+            );
         }
 
-        Ok(Self {
-            source: wgsl.to_string(),
-            modules: HashMap::new(),
-        })
+        Ok(Self::from_generated_source(name, &wgsl.to_wgsl()?))
     }
 
     /// Construct a new Sdf3DShader from the ShaderToy API
     ///
+    /// * `client`: Client for the ShaderToy API, also used to fetch the textures of channels
     /// * `shader_id`: Id of the ShaderToy shader, e.g. DldfR7
-    /// * `sdf`: Function name of the SDF function, e.g. `float sdf(vec3)`
+    /// * `options`: Render pass, SDF function and channel images
     pub async fn from_shadertoy_api(
+        client: &shadertoy::ShaderToyClient,
         shader_id: &str,
-        sdf: &str,
+        options: &shadertoy::ShaderToyOptions,
+    ) -> Result<Self, shadertoy::ShaderProcessingError> {
+        let shader = client.fetch_shader(shader_id).await?;
+        Self::from_shadertoy(client, &format!("ShaderToy {shader_id}"), &shader, options).await
+    }
+
+    /// Construct a new Sdf3DShader from a ShaderToy shader exported as JSON
+    ///
+    /// * `client`: Client for the ShaderToy API, used to fetch the textures of channels
+    /// * `path`: Path of the JSON file
+    /// * `options`: Render pass, SDF function and channel images
+    pub async fn from_shadertoy_json(
+        client: &shadertoy::ShaderToyClient,
+        path: impl AsRef<std::path::Path>,
+        options: &shadertoy::ShaderToyOptions,
+    ) -> Result<Self, shadertoy::ShaderProcessingError> {
+        let shader = shadertoy::Shader::from_json_file(&path)?;
+        let name = path.as_ref().display().to_string();
+        Self::from_shadertoy(client, &name, &shader, options).await
+    }
+
+    /// Construct a new Sdf3DShader from a ShaderToy shader, `name` is used in error messages
+    async fn from_shadertoy(
+        client: &shadertoy::ShaderToyClient,
+        name: &str,
+        shader: &shadertoy::Shader,
+        options: &shadertoy::ShaderToyOptions,
     ) -> Result<Self, shadertoy::ShaderProcessingError> {
-        let shader = shadertoy::Shader::from_api(shader_id).await?;
         log::info!("Shader: {}", shader.info.name);
         log::info!("Shader author: {}", shader.info.username);
 
-        let mut wgsl = shader.generate_wgsl_shader_code()?;
+        let channels = client.load_channels(shader, options).await?;
+        let mut wgsl = shader.generate_wgsl_shader_code(options.pass.as_deref(), &channels)?;
+        // The SDF is evaluated in compute shaders
+        wgsl.sample_level_zero();
 
-        wgsl.remove_function("fn main_1(")?;
-        wgsl.remove_function("fn main(")?;
+        let sdf = wgsl.find_sdf_function(options.sdf.as_deref())?;
+        log::info!("SDF function: {sdf}");
 
-        wgsl.remove_function("fn mainImage(")?;
-        wgsl.remove_line("@fragment"); // Remove fragment entry point
+        wgsl.remove_entry_points();
+        wgsl.remove_function("main")?;
+        wgsl.remove_function("mainImage")?;
 
-        if wgsl.has_function(sdf) {
-            if !wgsl.has_function("sdf3d") {
-                // Generate function wrapper
-                wgsl.add_line(
-                    format!("fn sdf3d(p: vec3<f32>) -> f32 {{ return {}(p); }}", sdf).as_str(),
-                );
-            }
-        } else {
-            return Err(shadertoy::ShaderProcessingError::MissingSdf(sdf.into()));
+        // Generate function wrapper, the WGSL writer renames functions like `sdf2` to `sdf2_`
+        let sdf = wgsl.wgsl_function_name(&sdf)?;
+        if sdf != "sdf3d" {
+            wgsl.add_line(
+                format!("fn sdf3d(p: vec3<f32>) -> f32 {{ return {}(p); }}", sdf).as_str(),
+            );
         }
 
         // Add function to compute the normal
         wgsl.add_line(include_str!("sdf3d_normal.wgsl"));
 
-        Ok(Self {
-            source: wgsl.to_string(),
-            modules: HashMap::new(),
-        })
+        let mut s = Self::from_generated_source(name, &wgsl.to_wgsl()?);
+        s.channels = channels;
+        Ok(s)
     }
 
-    fn add_module(
+    /// Register a module whose source is written by a handler
+    pub fn add_module(
         &mut self,
         name: &str,
         module: impl Fn(&mut dyn Write) -> std::io::Result<()> + 'static,
@@ -152,53 +668,355 @@ impl Sdf3DShader {
         self
     }
 
-    pub fn add_to_source(&mut self, source: &str) {
-        self.source += source;
+    /// Append source code, its lines are reported as lines of `name` in errors
+    pub fn add_to_source(&mut self, name: &str, source: &str) {
+        self.source_map.append(
+            &mut self.source,
+            source,
+            &SourceOrigin::Named(name.to_string()),
+        );
+    }
+
+    /// The source defines the function `name`, `false` if the source cannot be parsed
+    pub fn has_function(&self, name: &str) -> bool {
+        naga::front::wgsl::parse_str(&self.source)
+            .is_ok_and(|module| find_function(&module, name).is_some())
+    }
+
+    /// Append the function `sdf3d_vertex_normal(p: vec3f, eps: f32) -> vec3f` which estimates normals with `estimation`.
+    /// The module `sdf3d::normal` is appended as well if it is missing.
+    /// Returns the estimation in use, `Auto` is resolved depending on whether `sdf3d_gradient` is defined.
+    pub fn add_normal_estimation(
+        &mut self,
+        estimation: NormalEstimation,
+    ) -> Result<NormalEstimation, ShaderSourceError> {
+        // A source that does not parse is reported when the shader is validated
+        let module = naga::front::wgsl::parse_str(&self.source).ok();
+        let gradient = module.as_ref().and_then(|module| {
+            find_function(module, "sdf3d_gradient").map(|(_, gradient)| (module, gradient))
+        });
+        if let Some((module, gradient)) = gradient {
+            if !takes_vec3f(module, gradient, &VEC3F) {
+                return Err(ShaderSourceError::InvalidGradient {
+                    signature: function_signature(module, gradient),
+                });
+            }
+        }
+        let has_gradient = match module {
+            Some(_) => gradient.is_some(),
+            None => estimation == NormalEstimation::Analytic,
+        };
+        let estimation = match estimation {
+            NormalEstimation::Auto if has_gradient => NormalEstimation::Analytic,
+            NormalEstimation::Auto => NormalEstimation::Tetrahedral,
+            NormalEstimation::Analytic if !has_gradient => {
+                return Err(ShaderSourceError::MissingGradient)
+            }
+            estimation => estimation,
+        };
+
+        let has_normal = module
+            .as_ref()
+            .is_some_and(|module| find_function(module, "sdf3d_normal").is_some());
+        if !has_normal {
+            self.source_map.append(
+                &mut self.source,
+                BUILTIN_MODULES["sdf3d::normal"],
+                &SourceOrigin::Module("sdf3d::normal".into()),
+            );
+        }
+
+        let gradient = match estimation {
+            NormalEstimation::Central => "sdf3d_normal_central(p, eps)",
+            NormalEstimation::Analytic => "sdf3d_gradient(p)",
+            _ => "sdf3d_normal(p, eps)",
+        };
+        self.add_to_source(
+            "<normal>",
+            &format!(
+                "fn sdf3d_vertex_normal(p: vec3f, eps: f32) -> vec3f {{ return {gradient}; }}"
+            ),
+        );
+        Ok(estimation)
+    }
+
+    /// Argument of a `use` or `include` statement, e.g. `sdf3d::*` for `use sdf3d::*;`
+    fn statement_argument(trimmed: &str, keyword: &str) -> Option<String> {
+        let argument = trimmed.strip_prefix(keyword)?.strip_suffix(';')?;
+        if !argument.starts_with(|c: char| c.is_whitespace() || c == '"') {
+            return None;
+        }
+        Some(argument.replace('"', "").trim().to_string())
+    }
+
+    /// Relative path of the module file or namespace directory, e.g. `acme/gears` for `acme::gears`
+    fn module_relative_path(modulename: &str) -> PathBuf {
+        modulename
+            .split("::")
+            .filter(|component| !component.is_empty())
+            .collect()
+    }
+
+    /// The module name has no empty, `.` or `..` segments and no path separators,
+    /// so its file cannot be outside of the module paths
+    fn is_valid_module_name(modulename: &str) -> bool {
+        let name = match modulename.strip_suffix('*') {
+            Some("") => return true,
+            Some(prefix) => prefix.strip_suffix("::").unwrap_or(prefix),
+            None => modulename,
+        };
+        name.split("::")
+            .all(|segment| !matches!(segment, "" | "." | "..") && !segment.contains(['/', '\\']))
+    }
+
+    /// Modules imported by `use <modulename>;`.
+    /// A wildcard like `sdf3d::*` imports all modules in the namespace.
+    fn resolve_modules(&self, modulename: &str) -> Vec<(String, ModuleSource)> {
+        let Some(prefix) = modulename.strip_suffix('*') else {
+            if self.modules.contains_key(modulename) {
+                return vec![(modulename.to_string(), ModuleSource::Registered)];
+            }
+
+            let relative_path =
+                Self::module_relative_path(modulename).with_extension(MODULE_FILE_EXTENSION);
+            return self
+                .module_paths
+                .iter()
+                .map(|dir| dir.join(&relative_path))
+                .find(|path| self.file_exists(path))
+                .map(|path| vec![(modulename.to_string(), ModuleSource::File(path))])
+                .unwrap_or_default();
+        };
+
+        let mut modules: Vec<(String, ModuleSource)> = self
+            .modules
+            .keys()
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| (name.clone(), ModuleSource::Registered))
+            .collect();
+
+        let namespace = Self::module_relative_path(prefix);
+        for dir in &self.module_paths {
+            let Ok(entries) = std::fs::read_dir(dir.join(&namespace)) else {
+                continue;
+            };
+
+            for path in entries.flatten().map(|entry| entry.path()) {
+                if !path.is_file()
+                    || path
+                        .extension()
+                        .is_none_or(|ext| ext != MODULE_FILE_EXTENSION)
+                {
+                    continue;
+                }
+                let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+
+                let name = format!("{prefix}{stem}");
+                if !modules.iter().any(|(n, _)| *n == name) {
+                    modules.push((name, ModuleSource::File(path)));
+                }
+            }
+        }
+
+        modules.sort_by(|a, b| a.0.cmp(&b.0));
+        modules
+    }
+
+    /// Path of a file included from `file`
+    fn resolve_include(&self, file: &Path, include: &Path) -> Option<PathBuf> {
+        let dir = file.parent().unwrap_or(Path::new(""));
+        std::iter::once(dir)
+            .chain(self.include_paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(include))
+            .find(|path| self.file_exists(path))
     }
 
+    /// Source of a file from the virtual filesystem
+    fn virtual_file(&self, path: &Path) -> Option<String> {
+        self.file_loader.as_ref().and_then(|loader| loader(path))
+    }
+
+    /// Check if a file exists in the virtual filesystem or on disk
+    fn file_exists(&self, path: &Path) -> bool {
+        self.virtual_file(path).is_some() || path.is_file()
+    }
+
+    /// Read a file from the virtual filesystem or from disk
+    fn read_file(&self, path: &Path) -> Result<String, ShaderSourceError> {
+        match self.virtual_file(path) {
+            Some(text) => Ok(text),
+            None => std::fs::read_to_string(path).map_err(|error| ShaderSourceError::Io {
+                path: path.to_path_buf(),
+                error,
+            }),
+        }
+    }
+
+    /// Path that identifies a file to detect cycles and repeated includes.
+    /// Files on disk are identified by their canonical path, virtual files by their path.
+    fn file_id(path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// Read and append the source of a file, resolving its modules and includes
+    fn shader_source_file(
+        &self,
+        path: &Path,
+        reader: &mut SourceReader,
+        source: &mut String,
+        source_map: &mut SourceMap,
+    ) -> Result<(), ShaderSourceError> {
+        let text = self.read_file(path)?;
+        self.shader_source_input(path, &text, reader, source, source_map)
+    }
+
+    /// Append the source `text` of the file `path`, resolving its modules and includes
     fn shader_source_input(
         &self,
-        path: impl AsRef<std::path::Path>,
-        w: &mut dyn Write,
-    ) -> std::io::Result<()> {
-        match read_lines(&path) {
-            Ok(lines) => {
-                for line in lines.flatten() {
-                    let trimmed = line.trim();
-
-                    if trimmed.ends_with(';') {
-                        if trimmed.starts_with("use") {
-                            let modulename = trimmed
-                                .replacen("use", "", 1)
-                                .replace(['\"', ';'], "")
-                                .trim()
-                                .to_string();
-                            log::info!("{modulename}");
-                            if self.modules.contains_key(&modulename) {
-                                self.modules.get(&modulename).unwrap()(w)?;
-                            }
-                            continue;
-                        } else if trimmed.starts_with("include") {
-                            let filename = std::path::PathBuf::from(
-                                trimmed
-                                    .replacen("include", "", 1)
-                                    .replace(['\"', ';'], "")
-                                    .trim(),
+        path: &Path,
+        text: &str,
+        reader: &mut SourceReader,
+        source: &mut String,
+        source_map: &mut SourceMap,
+    ) -> Result<(), ShaderSourceError> {
+        let io_error = |error| ShaderSourceError::Io {
+            path: path.to_path_buf(),
+            error,
+        };
+
+        let file_id = Self::file_id(path);
+        reader.include_stack.push(file_id.clone());
+        reader.included_files.insert(file_id);
+        let origin = SourceOrigin::File(path.to_path_buf());
+
+        for (line_idx, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+
+            if let Some(modulename) = Self::statement_argument(trimmed, "use") {
+                if !Self::is_valid_module_name(&modulename) {
+                    return Err(ShaderSourceError::InvalidModule {
+                        file: path.to_path_buf(),
+                        line: line_idx + 1,
+                        module: modulename,
+                    });
+                }
+                let modules = self.resolve_modules(&modulename);
+                if modules.is_empty() {
+                    return Err(ShaderSourceError::UnknownModule {
+                        file: path.to_path_buf(),
+                        line: line_idx + 1,
+                        module: modulename,
+                    });
+                }
+
+                for (name, module_source) in modules {
+                    if !reader.imported_modules.insert(name.clone()) {
+                        continue;
+                    }
+
+                    log::info!("Import module {name}");
+                    match module_source {
+                        ModuleSource::Registered => {
+                            let mut w = Vec::new();
+                            self.modules[&name](&mut w).map_err(io_error)?;
+                            source_map.append(
+                                source,
+                                &String::from_utf8_lossy(&w),
+                                &SourceOrigin::Module(name),
                             );
-                            if filename != path.as_ref() {
-                                self.shader_source_input(filename, w)?;
-                            }
-                            continue;
+                        }
+                        ModuleSource::File(module_path) => {
+                            self.shader_source_file(&module_path, reader, source, source_map)?
                         }
                     }
-                    writeln!(w, "{}", line)?;
                 }
+                continue;
+            }
+
+            if let Some(filename) = Self::statement_argument(trimmed, "include") {
+                let include = PathBuf::from(filename);
+                let Some(include_path) = self.resolve_include(path, &include) else {
+                    return Err(ShaderSourceError::IncludeNotFound {
+                        file: path.to_path_buf(),
+                        line: line_idx + 1,
+                        include,
+                    });
+                };
+
+                let include_id = Self::file_id(&include_path);
+                if reader.include_stack.contains(&include_id) {
+                    return Err(ShaderSourceError::IncludeCycle {
+                        file: path.to_path_buf(),
+                        line: line_idx + 1,
+                        include,
+                    });
+                }
+                if !reader.included_files.contains(&include_id) {
+                    self.shader_source_file(&include_path, reader, source, source_map)?;
+                }
+                continue;
             }
-            Err(err) => {
-                log::error!("Could not include {:?}: {}", path.as_ref(), err);
+
+            if let Some(param) = Param::parse(line) {
+                let invalid_param = |message| ShaderSourceError::InvalidParam {
+                    file: path.to_path_buf(),
+                    line: line_idx + 1,
+                    message,
+                };
+                let mut param = param.map_err(invalid_param)?;
+                if reader.is_declared(&param.name) {
+                    return Err(invalid_param(format!("`{}` is declared twice", param.name)));
+                }
+
+                if let Some(value) = self.defines.get(&param.name) {
+                    param
+                        .set_value(value)
+                        .map_err(|message| ShaderSourceError::InvalidDefine {
+                            name: param.name.clone(),
+                            message,
+                        })?;
+                }
+
+                if !param.uniform {
+                    source_map.push_line(source, &param.declaration(), &origin, line_idx + 1);
+                }
+                reader.params.push(param);
+                continue;
             }
+
+            if let Some(text) = Text::parse(line) {
+                let invalid_text = |message| ShaderSourceError::InvalidText {
+                    file: path.to_path_buf(),
+                    line: line_idx + 1,
+                    message,
+                };
+                let mut text = text.map_err(invalid_text)?;
+                if reader.is_declared(&text.name) {
+                    return Err(invalid_text(format!("`{}` is declared twice", text.name)));
+                }
+
+                if let Some(value) = self.defines.get(&text.name) {
+                    text.set_value(value)
+                        .map_err(|message| ShaderSourceError::InvalidDefine {
+                            name: text.name.clone(),
+                            message,
+                        })?;
+                }
+
+                let segments = text::layout(&text.value).map_err(invalid_text)?;
+                let declaration = text.declaration(reader.text_segments.len(), segments.len());
+                source_map.push_line(source, &declaration, &origin, line_idx + 1);
+                reader.text_segments.extend(segments);
+                reader.texts.push(text);
+                continue;
+            }
+
+            source_map.push_line(source, line, &origin, line_idx + 1);
         }
 
+        reader.include_stack.pop();
         Ok(())
     }
 
@@ -208,19 +1026,680 @@ impl Sdf3DShader {
         write!(f, "{}", self.source)
     }
 
-    /// Return shader source as string
-    fn shader_source(&self, path: impl AsRef<std::path::Path>) -> String {
-        let mut w = Vec::new();
-        let _ = self.shader_source_input(path, &mut w);
+    /// Error for a module without `sdf3d`, suggesting the functions with the signature of an SDF
+    fn missing_sdf3d_error(module: &naga::Module) -> ShaderCompileError {
+        let candidates: Vec<_> = sdf_function_candidates(module)
+            .into_iter()
+            .filter(|name| name != "sdf3d")
+            .map(|name| format!("`{name}`"))
+            .collect();
+
+        let mut message = format!("The shader does not define `{SDF3D_SIGNATURE}`");
+        if !candidates.is_empty() {
+            message += &format!(
+                ". Functions with a matching signature: {}. Rename one of them to `sdf3d` or call it from `sdf3d`",
+                candidates.join(", ")
+            );
+        }
+        ShaderCompileError {
+            message,
+            labels: Vec::new(),
+        }
+    }
+
+    /// Check that `sdf3d` has the signature `fn sdf3d(p: vec3f) -> f32`
+    fn check_sdf3d_signature(&self, module: &naga::Module) -> Result<(), ShaderCompileError> {
+        let Some((handle, function)) = find_function(module, "sdf3d") else {
+            return Err(Self::missing_sdf3d_error(module));
+        };
+        if is_sdf_function(module, function) {
+            return Ok(());
+        }
+
+        let span = module.functions.get_span(handle);
+        Err(self.compile_error(
+            format!(
+                "`sdf3d` has the signature `{}`, expected `{SDF3D_SIGNATURE}`",
+                function_signature(module, function)
+            ),
+            std::iter::once((span, "wrong signature".to_string())),
+        ))
+    }
+
+    /// Map compiler error labels in the composed source back to the original sources
+    fn compile_error(
+        &self,
+        message: String,
+        spans: impl Iterator<Item = (naga::Span, String)>,
+    ) -> ShaderCompileError {
+        let labels = spans
+            .filter(|(span, _)| span.is_defined())
+            .map(|(span, label)| {
+                let location = span.location(&self.source);
+                let line = location.line_number as usize;
+                let source_line = self.source.lines().nth(line - 1).unwrap_or_default();
+                let (origin, line) = match self.source_map.location(line) {
+                    Some((origin, line)) => (Some(origin.clone()), line),
+                    None => (None, line),
+                };
+
+                ShaderErrorLabel {
+                    origin,
+                    line,
+                    column: location.line_position as usize,
+                    label,
+                    source_line: source_line.to_string(),
+                }
+            })
+            .collect();
+
+        ShaderCompileError { message, labels }
+    }
+
+    /// Parse and validate the shader source
+    pub fn validate(&self) -> Result<naga::Module, ShaderCompileError> {
+        let module = naga::front::wgsl::parse_str(&self.source).map_err(|err| {
+            // If `sdf3d` is called but not defined, the source parses with a stub for it
+            let stub = format!(
+                "{}\nfn sdf3d(p: vec3f) -> f32 {{ return 0.0; }}\n",
+                self.source
+            );
+            match naga::front::wgsl::parse_str(&stub) {
+                Ok(module) => Self::missing_sdf3d_error(&module),
+                Err(_) => self.compile_error(
+                    err.message().to_string(),
+                    err.labels().map(|(span, label)| (span, label.to_string())),
+                ),
+            }
+        })?;
+        self.check_sdf3d_signature(&module)?;
+
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .map_err(|err| {
+            let mut message = err.as_inner().to_string();
+            let mut source = std::error::Error::source(err.as_inner());
+            while let Some(err) = source {
+                message += &format!(": {err}");
+                source = err.source();
+            }
+            self.compile_error(message, err.spans().cloned())
+        })?;
 
-        std::str::from_utf8(w.as_slice()).unwrap().to_string()
+        Ok(module)
     }
 
-    /// Create a `wgpu::ShaderModule`
-    pub fn create_shader_module(&self, device: &wgpu::Device) -> wgpu::ShaderModule {
-        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+    /// Validate the shader source and create a `wgpu::ShaderModule`
+    pub fn create_shader_module(
+        &self,
+        device: &wgpu::Device,
+    ) -> Result<wgpu::ShaderModule, ShaderCompileError> {
+        self.validate()?;
+
+        Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: None,
             source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(self.source.as_str())),
-        })
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_file(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn unknown_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(dir.path(), "a.sdf3d", "// test\nuse sdf3d::unknown;\n");
+
+        match Sdf3DShader::from_path(&path) {
+            Err(ShaderSourceError::UnknownModule { file, line, module }) => {
+                assert_eq!(file, path);
+                assert_eq!(line, 2);
+                assert_eq!(module, "sdf3d::unknown");
+            }
+            _ => panic!("Expected unknown module error"),
+        }
+    }
+
+    #[test]
+    fn wildcard_module_imported_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(
+            dir.path(),
+            "a.sdf3d",
+            "use sdf3d::*;\nuse sdf3d::primitives;\nuse sdf::*;\n",
+        );
+
+        let shader = Sdf3DShader::from_path(path).unwrap();
+        assert_eq!(shader.source.matches("fn sdf3d_torus(").count(), 1);
+        assert_eq!(shader.source.matches("fn sdf3d_normal(").count(), 1);
+        assert_eq!(shader.source.matches("fn sdf_op_smooth_union(").count(), 1);
+
+        // The default shader knows the built-in modules, too
+        let mut shader = Sdf3DShader::default();
+        shader.load_str("<string>", "use sdf3d::*;").unwrap();
+        assert!(shader.source.contains("fn sdf3d_torus("));
+    }
+
+    #[test]
+    fn include_relative_and_search_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "sub/b.wgsl", "include \"c.wgsl\";\nfn b() {}\n");
+        write_file(dir.path(), "sub/c.wgsl", "fn c() {}\n");
+        write_file(lib.path(), "d.wgsl", "fn d() {}\n");
+        let path = write_file(
+            dir.path(),
+            "a.sdf3d",
+            "include \"sub/b.wgsl\";\ninclude \"d.wgsl\";\n",
+        );
+
+        assert!(matches!(
+            Sdf3DShader::from_path(&path),
+            Err(ShaderSourceError::IncludeNotFound { line: 2, .. })
+        ));
+
+        let mut shader = Sdf3DShader::new();
+        shader.add_include_path(lib.path());
+        shader.load_file(&path).unwrap();
+        assert_eq!(shader.source, "fn c() {}\nfn b() {}\nfn d() {}\n");
+    }
+
+    #[test]
+    fn include_once() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "b.wgsl", "fn b() {}\n");
+        write_file(dir.path(), "c.wgsl", "include \"b.wgsl\";\n");
+        let path = write_file(
+            dir.path(),
+            "a.sdf3d",
+            "include \"b.wgsl\";\ninclude \"c.wgsl\";\n",
+        );
+
+        let shader = Sdf3DShader::from_path(path).unwrap();
+        assert_eq!(shader.source, "fn b() {}\n");
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "b.wgsl", "include \"c.wgsl\";\n");
+        let c = write_file(dir.path(), "c.wgsl", "\ninclude \"b.wgsl\";\n");
+        let path = write_file(dir.path(), "a.sdf3d", "include \"b.wgsl\";\n");
+
+        match Sdf3DShader::from_path(path) {
+            Err(ShaderSourceError::IncludeCycle { file, line, .. }) => {
+                assert_eq!(file, c);
+                assert_eq!(line, 2);
+            }
+            _ => panic!("Expected include cycle error"),
+        }
+    }
+
+    #[test]
+    fn module_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = tempfile::tempdir().unwrap();
+        write_file(
+            lib.path(),
+            "acme/gears.wgsl",
+            "use acme::util;\nfn gear() {}\n",
+        );
+        write_file(lib.path(), "acme/util.wgsl", "fn util() {}\n");
+        write_file(lib.path(), "acme/readme.txt", "Not a module");
+        let path = write_file(dir.path(), "a.sdf3d", "use acme::gears;\nuse acme::*;\n");
+
+        assert!(matches!(
+            Sdf3DShader::from_path(&path),
+            Err(ShaderSourceError::UnknownModule { line: 1, .. })
+        ));
+
+        let mut shader = Sdf3DShader::new();
+        shader.add_module_path(lib.path());
+        shader.add_module_source("acme::shapes", "fn shape() {}");
+        shader.load_file(&path).unwrap();
+        assert_eq!(shader.source, "fn util() {}\nfn gear() {}\nfn shape() {}\n");
+    }
+
+    #[test]
+    fn invalid_module_names() {
+        let lib = tempfile::tempdir().unwrap();
+        write_file(lib.path(), "secret.wgsl", "fn secret() {}\n");
+        let lib_path = lib.path().join("lib");
+        std::fs::create_dir(&lib_path).unwrap();
+
+        for module in [
+            "..::secret",
+            "acme::..::..::secret",
+            "acme::.::x",
+            "acme::::x",
+            "..::*",
+        ] {
+            let path = write_file(lib.path(), "a.sdf3d", &format!("use {module};\n"));
+            let mut shader = Sdf3DShader::new();
+            shader.add_module_path(&lib_path);
+            match shader.load_file(&path) {
+                Err(ShaderSourceError::InvalidModule {
+                    line, module: m, ..
+                }) => {
+                    assert_eq!(line, 1);
+                    assert_eq!(m, module);
+                }
+                _ => panic!("Expected invalid module error for `{module}`"),
+            }
+        }
+    }
+
+    #[test]
+    fn registered_module_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = tempfile::tempdir().unwrap();
+        write_file(lib.path(), "sdf/op.wgsl", "fn not_builtin() {}\n");
+        let path = write_file(dir.path(), "a.sdf3d", "use sdf::op;\n");
+
+        let mut shader = Sdf3DShader::new();
+        shader.add_module_path(lib.path());
+        shader.load_file(&path).unwrap();
+        assert!(shader.source.contains("fn sdf_op_smooth_union("));
+        assert!(!shader.source.contains("fn not_builtin("));
+    }
+
+    #[test]
+    fn compile_error_source_map() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(
+            dir.path(),
+            "b.wgsl",
+            "fn b() -> f32 {\n    return 1.0;\n}\n",
+        );
+        let path = write_file(
+            dir.path(),
+            "a.sdf3d",
+            "use sdf::op;\ninclude \"b.wgsl\";\nfn sdf3d(p: vec3f) -> f32 {\n    return b() + c;\n}\n",
+        );
+
+        let mut shader = Sdf3DShader::from_path(&path).unwrap();
+        shader.add_to_source("footer.wgsl", "fn footer() {}");
+        let err = shader.validate().unwrap_err();
+
+        let label = &err.labels[0];
+        assert_eq!(label.origin, Some(SourceOrigin::File(path)));
+        assert_eq!((label.line, label.column), (4, 18));
+        assert_eq!(label.source_line, "    return b() + c;");
+
+        let mut shader = Sdf3DShader::new();
+        shader.add_to_source("a.wgsl", "fn a() -> f32 {\n    let x: i32 = 1.0;\n}");
+        let err = shader.validate().unwrap_err();
+        let label = &err.labels[0];
+        assert_eq!(label.origin, Some(SourceOrigin::Named("a.wgsl".into())));
+        assert_eq!(label.line, 2);
+    }
+
+    #[test]
+    fn sdf3d_signature() {
+        let caller = "fn caller() -> f32 { return sdf3d(vec3f()); }";
+
+        let mut shader = Sdf3DShader::from_wgsl_str(
+            "fn map(p: vec3f) -> f32 { return length(p); }\nfn de(q: vec3<f32>) -> f32 { return 0.0; }\nfn other(p: vec2f) -> f32 { return 0.0; }",
+        )
+        .unwrap();
+        let err = shader.validate().unwrap_err();
+        assert!(err.message.contains("`map`, `de`"), "{}", err.message);
+        assert!(!err.message.contains("other"));
+        shader.add_to_source("caller.wgsl", caller);
+        assert_eq!(shader.validate().unwrap_err().message, err.message);
+
+        let mut shader =
+            Sdf3DShader::from_wgsl_str("\nfn sdf3d(p: vec2f) -> f32 { return length(p); }")
+                .unwrap();
+        shader.add_to_source(
+            "caller.wgsl",
+            "fn caller() -> f32 { return sdf3d(vec2f()); }",
+        );
+        let err = shader.validate().unwrap_err();
+        assert_eq!(
+            err.message,
+            "`sdf3d` has the signature `fn sdf3d(vec2<f32>) -> f32`, expected `fn sdf3d(p: vec3f) -> f32`"
+        );
+        assert_eq!(err.labels[0].line, 2);
+
+        // Other errors are reported as usual
+        let mut shader = Sdf3DShader::from_wgsl_str("fn a() { let x: i32 = 1.0; }").unwrap();
+        shader.add_to_source("caller.wgsl", caller);
+        assert!(!shader.validate().unwrap_err().message.contains("sdf3d"));
+    }
+
+    #[test]
+    fn from_str_with_virtual_files() {
+        let mut files = HashMap::new();
+        files.insert(
+            PathBuf::from("lib/shapes.wgsl"),
+            "fn shape() {}".to_string(),
+        );
+        files.insert(PathBuf::from("acme/gears.wgsl"), "fn gear() {}".to_string());
+
+        let mut shader = Sdf3DShader::new();
+        shader
+            .add_include_path("lib")
+            .add_module_path("")
+            .set_file_loader(move |path| files.get(path).cloned());
+        shader
+            .load_str(
+                "virtual.sdf3d",
+                "use acme::gears;\ninclude \"shapes.wgsl\";\nfn sdf3d() {}",
+            )
+            .unwrap();
+        assert_eq!(
+            shader.source,
+            "fn gear() {}\nfn shape() {}\nfn sdf3d() {}\n"
+        );
+
+        assert!(matches!(
+            Sdf3DShader::from_wgsl_str("include \"missing.wgsl\";"),
+            Err(ShaderSourceError::IncludeNotFound { line: 1, .. })
+        ));
+
+        let shader = Sdf3DShader::from_reader("use sdf::op;\n".as_bytes()).unwrap();
+        assert!(shader.source.contains("fn sdf_op_smooth_union("));
+    }
+
+    #[test]
+    fn from_glsl_str() {
+        let glsl = r#"
+            float sdf(vec3 p) { return length(p) - 1.0; }
+
+            void main() {}
+        "#;
+
+        let shader = Sdf3DShader::from_glsl_str(glsl, Some("sdf")).unwrap();
+        assert!(shader.source.contains("fn sdf3d(p: vec3<f32>) -> f32"));
+        assert!(matches!(
+            Sdf3DShader::from_glsl_str(glsl, Some("map")),
+            Err(shadertoy::ShaderProcessingError::MissingSdf(_))
+        ));
+
+        // No wrapper is generated for a GLSL function `sdf3d`
+        let shader =
+            Sdf3DShader::from_glsl_str("float sdf3d(vec3 p) { return p.x; } void main() {}", None)
+                .unwrap();
+        assert_eq!(shader.source.matches("fn sdf3d(").count(), 1);
+        assert!(matches!(
+            Sdf3DShader::from_glsl_fragment_shader("does/not/exist.frag", Some("sdf")),
+            Err(shadertoy::ShaderProcessingError::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn from_shadertoy_json() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/sphere_box.shadertoy.json"
+        );
+        let client = shadertoy::ShaderToyClient::new().unwrap();
+        let shader = pollster::block_on(Sdf3DShader::from_shadertoy_json(
+            &client,
+            path,
+            &shadertoy::ShaderToyOptions::default(),
+        ))
+        .unwrap();
+        assert!(shader
+            .source
+            .contains("fn sdf3d(p: vec3<f32>) -> f32 { return map(p); }"));
+        assert!(!shader.source.contains("fn mainImage("));
+        shader.validate().unwrap();
+
+        // Shaders in the cache are read without accessing the ShaderToy API
+        let dir = tempfile::tempdir().unwrap();
+        let cache = shadertoy::ShaderCache::new(dir.path());
+        cache
+            .store("sdf2mt", &std::fs::read_to_string(path).unwrap())
+            .unwrap();
+        let mut client = shadertoy::ShaderToyClient::new().unwrap();
+        client
+            .set_api_url("http://localhost:9/unreachable")
+            .set_cache(Some(cache));
+        let cached = pollster::block_on(Sdf3DShader::from_shadertoy_api(
+            &client,
+            "sdf2mt",
+            &shadertoy::ShaderToyOptions {
+                pass: Some("Image".into()),
+                sdf: Some("map".into()),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+        assert_eq!(cached.source, shader.source);
+    }
+
+    #[test]
+    fn from_shadertoy_channels() {
+        let dir = tempfile::tempdir().unwrap();
+        let noise = dir.path().join("noise.png");
+        crate::png::image_data_to_file(vec![128; 4 * 4 * 4], (4, 4), &noise);
+        let shader = shadertoy::Shader::from_json(
+            r#"{"ver": "0.1", "info": {"id": "noise1", "date": "0", "viewed": 0, "name": "Noise",
+            "username": "user", "description": "", "likes": 0, "published": 3, "flags": 0,
+            "usePreview": 0, "tags": [], "hasliked": 0}, "renderpass": [{"inputs": [], "outputs": [],
+            "code": "float map(vec3 p) { return length(p) - texture(iChannel0, p.xy / iChannelResolution[0].xy).x; }\nvoid mainImage(out vec4 c, in vec2 f) {}",
+            "name": "Image", "description": "", "type": "image"}]}"#,
+        )
+        .unwrap();
+
+        let client = shadertoy::ShaderToyClient::new().unwrap();
+        let mut options = shadertoy::ShaderToyOptions::default();
+        assert!(pollster::block_on(Sdf3DShader::from_shadertoy(
+            &client, "noise1", &shader, &options
+        ))
+        .is_err());
+
+        // The channel is bound in its own group and sampled at mip level 0 in the compute shader
+        options.channels[0] = Some(noise);
+        let s = pollster::block_on(Sdf3DShader::from_shadertoy(
+            &client, "noise1", &shader, &options,
+        ))
+        .unwrap();
+        assert_eq!(s.channels()[0].as_ref().unwrap().image.size, [4, 4, 1]);
+        assert!(s.source.contains("@group(2) @binding(0)"));
+        assert!(s.source.contains("@group(2) @binding(1)"));
+        assert!(s.source.contains("textureSampleLevel("));
+        s.validate().unwrap();
+    }
+
+    #[test]
+    fn detect_glsl_sdf() {
+        let detect = |glsl: &str| {
+            Sdf3DShader::from_glsl_str(&format!("{glsl}\nvoid main() {{}}"), None)
+                .map(|shader| shader.source)
+        };
+
+        // The only candidate is picked, whatever its name
+        let source = detect("float torus(vec3 p) { return length(p) - 1.0; }").unwrap();
+        assert!(source.contains("return torus(p); }"));
+
+        // An obvious name is picked among several candidates
+        let source = detect(
+            "float hash(vec3 p) { return fract(p.x); }
+             float map(vec3 p) { return length(p) - hash(p); }",
+        )
+        .unwrap();
+        assert!(source.contains("return map(p); }"));
+
+        // Functions with other signatures are no candidates
+        let source = detect(
+            "vec2 scene(vec3 p) { return vec2(length(p), 1.0); }
+             float body(in vec3 p) { return scene(p).x; }",
+        )
+        .unwrap();
+        assert!(source.contains("return body(p); }"));
+
+        match detect(
+            "float a(vec3 p) { return p.x; }
+             float b(vec3 p) { return p.y; }",
+        ) {
+            Err(shadertoy::ShaderProcessingError::AmbiguousSdf(candidates)) => {
+                assert_eq!(candidates, ["a", "b"]);
+            }
+            _ => panic!("expected AmbiguousSdf"),
+        }
+        assert!(matches!(
+            detect("float a(vec2 p) { return p.x; }"),
+            Err(shadertoy::ShaderProcessingError::NoSdfCandidate)
+        ));
+    }
+
+    #[test]
+    fn params() {
+        let source = "@param(min = 1.0) const SIZE = 2.0; // Size\nfn sdf3d() {}\n";
+
+        let mut shader = Sdf3DShader::new();
+        shader.load_str("a.sdf3d", source).unwrap();
+        assert_eq!(shader.source, "const SIZE: f32 = 2.0;\nfn sdf3d() {}\n");
+        assert_eq!(shader.params().len(), 1);
+        assert_eq!(shader.params()[0].description, "Size");
+
+        shader.define("SIZE", "3");
+        shader.load_str("a.sdf3d", source).unwrap();
+        assert_eq!(shader.source, "const SIZE: f32 = 3.0;\nfn sdf3d() {}\n");
+
+        shader.define("SIZE", "0.5");
+        assert!(matches!(
+            shader.load_str("a.sdf3d", source),
+            Err(ShaderSourceError::InvalidDefine { .. })
+        ));
+
+        let mut shader = Sdf3DShader::new();
+        shader.define("WIDTH", "3");
+        assert!(matches!(
+            shader.load_str("a.sdf3d", source),
+            Err(ShaderSourceError::UnknownDefine { .. })
+        ));
+
+        assert!(matches!(
+            Sdf3DShader::from_wgsl_str("@param const A = 1;\n@param const A = 2;"),
+            Err(ShaderSourceError::InvalidParam { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn uniform_params() {
+        let mut shader = Sdf3DShader::new();
+        shader.define("SIZE", "3");
+        shader
+            .load_str(
+                "a.sdf3d",
+                "@param(uniform) const SIZE = 2.0;\nfn sdf3d(p: vec3f) -> f32 { return length(p) - params.SIZE; }\n",
+            )
+            .unwrap();
+        assert!(shader.source.contains("var<uniform> params: Params;"));
+        assert!(!shader.source.contains("const SIZE"));
+        assert_eq!(shader.params()[0].value, crate::param::ParamValue::F32(3.0));
+        shader.validate().unwrap();
+    }
+
+    #[test]
+    fn normal_estimation() {
+        let sdf = "fn sdf3d(p: vec3f) -> f32 { return length(p) - 1.0; }\n";
+        let gradient = "fn sdf3d_gradient(p: vec3f) -> vec3f { return normalize(p); }\n";
+
+        let mut shader = Sdf3DShader::from_wgsl_str(sdf).unwrap();
+        assert!(matches!(
+            shader.add_normal_estimation(NormalEstimation::Analytic),
+            Err(ShaderSourceError::MissingGradient)
+        ));
+        assert_eq!(
+            shader
+                .add_normal_estimation(NormalEstimation::Auto)
+                .unwrap(),
+            NormalEstimation::Tetrahedral
+        );
+        // The module `sdf3d::normal` is added because it was not imported
+        assert!(shader.has_function("sdf3d_normal_central"));
+        assert!(shader.has_function("sdf3d_vertex_normal"));
+        shader.validate().unwrap();
+
+        let mut shader = Sdf3DShader::from_wgsl_str(&format!("use sdf3d::normal;\n{sdf}")).unwrap();
+        shader
+            .add_normal_estimation(NormalEstimation::Central)
+            .unwrap();
+        assert!(shader
+            .source
+            .contains("return sdf3d_normal_central(p, eps);"));
+        shader.validate().unwrap();
+
+        let mut shader = Sdf3DShader::from_wgsl_str(&format!("{sdf}{gradient}")).unwrap();
+        assert_eq!(
+            shader
+                .add_normal_estimation(NormalEstimation::Auto)
+                .unwrap(),
+            NormalEstimation::Analytic
+        );
+        shader.validate().unwrap();
+
+        // A gradient with the wrong signature is not used
+        let mut shader = Sdf3DShader::from_wgsl_str(&format!(
+            "{sdf}fn sdf3d_gradient(p: vec3f) -> f32 {{ return 1.0; }}\n"
+        ))
+        .unwrap();
+        let err = shader
+            .add_normal_estimation(NormalEstimation::Auto)
+            .unwrap_err();
+        assert!(matches!(err, ShaderSourceError::InvalidGradient { .. }));
+        assert!(err.to_string().starts_with(
+            "`sdf3d_gradient` has the signature `fn sdf3d_gradient(vec3<f32>) -> f32`"
+        ));
+    }
+
+    #[test]
+    fn texts() {
+        let source = "use sdf3d::text;\n@text const A = \"AB\";\n@text const B = \"C\"; // Label\nfn sdf3d(p: vec3f) -> f32 { return sdf3d_text_extrude(p, B, 0.2, 0.02, 0.1); }\n";
+        let mut shader = Sdf3DShader::new();
+        shader.define("A", "HI");
+        shader.load_str("a.sdf3d", source).unwrap();
+        assert_eq!(shader.texts().len(), 2);
+        assert_eq!(shader.texts()[0].value, "HI");
+        assert_eq!(shader.texts()[1].description, "Label");
+        // H and I have 3 strokes each, C has 7
+        assert!(shader.source.contains("const A: vec2u = vec2u(0u, 6u);"));
+        assert!(shader.source.contains("const B: vec2u = vec2u(6u, 7u);"));
+        assert!(shader
+            .source
+            .contains("SDF3D_TEXT_SEGMENTS: array<vec4f, 13>"));
+        shader.validate().unwrap();
+
+        // Without texts, the module still compiles
+        let shader = Sdf3DShader::from_wgsl_str(
+            "use sdf3d::text;\nfn sdf3d(p: vec3f) -> f32 { return length(p) - 1.0; }",
+        )
+        .unwrap();
+        shader.validate().unwrap();
+
+        let mut shader = Sdf3DShader::new();
+        shader.define("B", "€");
+        assert!(matches!(
+            shader.load_str("a.sdf3d", source),
+            Err(ShaderSourceError::InvalidDefine { .. })
+        ));
+        let err = Sdf3DShader::from_wgsl_str("@param const A = 1;\n@text const A = \"A\";")
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            ShaderSourceError::InvalidText { line: 2, .. }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "<string>:2: Invalid text: `A` is declared twice"
+        );
     }
 }
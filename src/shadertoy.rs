@@ -1,7 +1,31 @@
-static API_KEY: &str = "rdnjhn";
-
 use serde::Deserialize;
 
+use crate::channel::{self, Channel, CHANNEL_COUNT};
+
+/// Default key for the ShaderToy API
+const DEFAULT_API_KEY: &str = "rdnjhn";
+
+/// Default base URL of the ShaderToy API
+pub const DEFAULT_API_URL: &str = "https://www.shadertoy.com/api/v1";
+
+/// Default URL media files like textures are fetched from, followed by the source of the input
+pub const DEFAULT_MEDIA_URL: &str = "https://www.shadertoy.com";
+
+/// Environment variable with the ShaderToy API key
+pub const API_KEY_ENV: &str = "SDF2MESH_SHADERTOY_API_KEY";
+
+/// Environment variable with the base URL of the ShaderToy API
+pub const API_URL_ENV: &str = "SDF2MESH_SHADERTOY_API_URL";
+
+/// Timeout for establishing a connection to the ShaderToy API
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Timeout for a whole request to the ShaderToy API
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Delay before the first retry of a failed request, doubled for each further retry
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
 #[derive(Debug, Deserialize, Default)]
 #[allow(unused)]
 pub struct ShaderInfo {
@@ -31,6 +55,22 @@ pub struct Sampler {
     internal: String,
 }
 
+impl Sampler {
+    fn filter(&self) -> channel::Filter {
+        match self.filter.as_str() {
+            "nearest" => channel::Filter::Nearest,
+            _ => channel::Filter::Linear,
+        }
+    }
+
+    fn wrap(&self) -> channel::Wrap {
+        match self.wrap.as_str() {
+            "clamp" => channel::Wrap::Clamp,
+            _ => channel::Wrap::Repeat,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Default)]
 #[allow(unused)]
 pub struct ShaderInput {
@@ -49,6 +89,12 @@ pub struct ShaderOutput {
     channel: i32,
 }
 
+/// Type of the render pass with code shared by all passes
+const COMMON_PASS: &str = "common";
+
+/// Type of the render pass that renders the image
+const IMAGE_PASS: &str = "image";
+
 #[derive(Debug, Deserialize, Default)]
 #[allow(unused)]
 pub struct RenderPass {
@@ -69,7 +115,9 @@ pub struct Shader {
 
 #[derive(Debug)]
 pub enum ShaderProcessingError {
+    IoError(std::io::Error),
     RequestError(reqwest::Error),
+    JsonError(serde_json::Error),
     ShaderError(String),
     ParseErrors(naga::front::glsl::ParseErrors),
     WgslError(naga::back::wgsl::Error),
@@ -77,11 +125,92 @@ pub enum ShaderProcessingError {
 
     /// Error when the SDF is missing in the shader
     MissingSdf(String),
+
+    /// Error when the SDF cannot be detected, contains the functions with the signature `float(vec3)`
+    AmbiguousSdf(Vec<String>),
+
+    /// Error when the shader has no function with the signature `float(vec3)`
+    NoSdfCandidate,
+
+    /// Error when the render pass of the SDF reads the output of a buffer from a channel
+    BufferInput {
+        pass: String,
+        buffer: String,
+        channel: i32,
+    },
+
+    /// Error when the render pass of the SDF reads a channel without input or with an input of an unsupported type
+    UnsupportedChannel {
+        channel: usize,
+        input: Option<String>,
+    },
 }
 
+impl std::fmt::Display for ShaderProcessingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderProcessingError::IoError(err) => write!(f, "{err}"),
+            ShaderProcessingError::RequestError(err) => write!(f, "{err}"),
+            ShaderProcessingError::JsonError(err) => write!(f, "{err}"),
+            ShaderProcessingError::ShaderError(err) => write!(f, "{err}"),
+            ShaderProcessingError::ParseErrors(err) => write!(f, "{err}"),
+            ShaderProcessingError::WgslError(err) => write!(f, "{err}"),
+            ShaderProcessingError::ValidationError(err) => write!(f, "{err}"),
+            ShaderProcessingError::NoSdfCandidate => write!(
+                f,
+                "The shader does not define a function with the signature `float(vec3)`"
+            ),
+            ShaderProcessingError::MissingSdf(sdf) => {
+                write!(f, "The shader does not define the SDF function `{sdf}`")
+            }
+            ShaderProcessingError::BufferInput {
+                pass,
+                buffer,
+                channel,
+            } => write!(
+                f,
+                "The render pass `{pass}` reads `{buffer}` from `iChannel{channel}`, buffers are not supported"
+            ),
+            ShaderProcessingError::UnsupportedChannel {
+                channel,
+                input: Some(input),
+            } => write!(
+                f,
+                "`iChannel{channel}` reads an input of type `{input}`, which is not supported"
+            ),
+            ShaderProcessingError::UnsupportedChannel {
+                channel,
+                input: None,
+            } => write!(f, "`iChannel{channel}` is read but has no input"),
+            ShaderProcessingError::AmbiguousSdf(candidates) => write!(
+                f,
+                "Cannot detect the SDF function, candidates with the signature `float(vec3)`: {}",
+                candidates
+                    .iter()
+                    .map(|name| format!("`{name}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for ShaderProcessingError {
+    fn from(error: std::io::Error) -> Self {
+        ShaderProcessingError::IoError(error)
+    }
+}
+
+/// The URL is removed from the error because it may contain the API key
 impl From<reqwest::Error> for ShaderProcessingError {
     fn from(error: reqwest::Error) -> Self {
-        ShaderProcessingError::RequestError(error)
+        ShaderProcessingError::RequestError(error.without_url())
+    }
+}
+
+impl From<serde_json::Error> for ShaderProcessingError {
+    fn from(error: serde_json::Error) -> Self {
+        ShaderProcessingError::JsonError(error)
     }
 }
 
@@ -116,28 +245,95 @@ pub enum ShaderToyApiResponse {
 }
 
 impl Shader {
-    pub fn fetch_code_from_last_pass(&self) -> Option<String> {
-        let mut code = String::new();
-        for pass in &self.renderpass {
-            code += &pass.code;
-        }
-        Some(code)
+    /// Render pass that defines the SDF, the pass named `name` (e.g. `Buffer A`) or the image pass
+    pub fn sdf_pass(&self, name: Option<&str>) -> Result<&RenderPass, ShaderProcessingError> {
+        let pass = match name {
+            Some(name) => self
+                .renderpass
+                .iter()
+                .find(|pass| pass.name.eq_ignore_ascii_case(name)),
+            None => self
+                .renderpass
+                .iter()
+                .find(|pass| pass.r#type == IMAGE_PASS),
+        };
+        pass.ok_or_else(|| {
+            format!(
+                "The shader has no render pass `{}`, render passes: {}",
+                name.unwrap_or("Image"),
+                self.renderpass
+                    .iter()
+                    .map(|pass| format!("`{}`", pass.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+            .into()
+        })
     }
 
-    pub async fn from_api(shader_id: &str) -> Result<Self, ShaderProcessingError> {
-        let response = reqwest::get(format!(
-            "https://www.shadertoy.com/api/v1/shaders/{shader_id}?key={API_KEY}"
-        ))
-        .await?;
+    /// Code of the common pass followed by the code of the render pass that defines the SDF.
+    ///
+    /// Buffers are not supported, so the code must not read the output of a buffer pass from a channel,
+    /// unless an image is bound to the channel instead (`bound[channel]`).
+    pub fn fetch_code(
+        &self,
+        pass: Option<&str>,
+        bound: &[bool],
+    ) -> Result<String, ShaderProcessingError> {
+        let pass = self.sdf_pass(pass)?;
 
-        let shader = response.json::<ShaderToyApiResponse>().await?;
+        let mut code = String::new();
+        for common in self
+            .renderpass
+            .iter()
+            .filter(|pass| pass.r#type == COMMON_PASS)
+        {
+            code += &common.code;
+            code += "\n";
+        }
+        code += &pass.code;
+
+        let is_bound = |channel: i32| {
+            usize::try_from(channel).is_ok_and(|channel| bound.get(channel) == Some(&true))
+        };
+        for input in pass
+            .inputs
+            .iter()
+            .filter(|input| input.ctype == "buffer" && !is_bound(input.channel))
+        {
+            if code.contains(&format!("iChannel{}", input.channel)) {
+                let buffer = self
+                    .renderpass
+                    .iter()
+                    .find(|pass| pass.outputs.iter().any(|output| output.id == input.id))
+                    .map_or("a buffer", |buffer| buffer.name.as_str());
+                return Err(ShaderProcessingError::BufferInput {
+                    pass: pass.name.clone(),
+                    buffer: buffer.into(),
+                    channel: input.channel,
+                });
+            }
+        }
 
-        match shader {
-            ShaderToyApiResponse::Shader(shader) => Ok(shader),
-            ShaderToyApiResponse::Error(error) => Err(error.into()),
+        Ok(code)
+    }
+
+    /// Parse a shader from JSON, either a response of the ShaderToy API or a single shader
+    pub fn from_json(json: &str) -> Result<Self, ShaderProcessingError> {
+        match serde_json::from_str::<ShaderToyApiResponse>(json) {
+            Ok(ShaderToyApiResponse::Shader(shader)) => Ok(shader),
+            Ok(ShaderToyApiResponse::Error(error)) => Err(error.into()),
+            Err(err) => serde_json::from_str::<Shader>(json).map_err(|_| err.into()),
         }
     }
 
+    /// Read a shader from a JSON file exported from ShaderToy or written by the ShaderToy API
+    pub fn from_json_file(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, ShaderProcessingError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
     pub fn default_uniform_block() -> &'static str {
         r#"
         layout(binding=0) uniform vec3      iResolution;           // viewport resolution (in pixels)
@@ -151,12 +347,19 @@ impl Shader {
         "#
     }
 
-    pub fn generate_wgsl_shader_code(&self) -> Result<WgslShaderCode, ShaderProcessingError> {
+    /// Convert the code of the render pass `pass`, see `fetch_code`, with the textures of `channels`
+    pub fn generate_wgsl_shader_code(
+        &self,
+        pass: Option<&str>,
+        channels: &[Option<Channel>],
+    ) -> Result<WgslShaderCode, ShaderProcessingError> {
         let mut glsl = String::from("#version 450 core\n");
 
         glsl += Shader::default_uniform_block();
+        glsl += &channel::glsl_declarations(channels);
 
-        let shader_code = &self.fetch_code_from_last_pass().unwrap();
+        let bound: Vec<bool> = channels.iter().map(Option::is_some).collect();
+        let shader_code = &self.fetch_code(pass, &bound)?;
         glsl += shader_code;
 
         // We add an empty main function to the shader so that naga can compile it to valid WGSL
@@ -166,8 +369,8 @@ impl Shader {
     }
 }
 
-pub fn convert_glsl_to_wgsl(glsl: &str) -> Result<String, ShaderProcessingError> {
-    use naga::back::wgsl::WriterFlags;
+/// Parse a GLSL fragment shader into a validated naga module
+fn parse_glsl(glsl: &str) -> Result<naga::Module, ShaderProcessingError> {
     use naga::front::glsl::{Frontend, Options};
     use naga::ShaderStage;
 
@@ -176,228 +379,940 @@ pub fn convert_glsl_to_wgsl(glsl: &str) -> Result<String, ShaderProcessingError>
     let options = Options::from(ShaderStage::Fragment);
 
     let module = frontend.parse(&options, glsl)?;
+    validate(&module)?;
 
-    // Write to WGSL
-    let mut wgsl = String::new();
-    let mut wgsl_writer = naga::back::wgsl::Writer::new(&mut wgsl, WriterFlags::empty());
+    Ok(module)
+}
 
+fn validate(module: &naga::Module) -> Result<naga::valid::ModuleInfo, ShaderProcessingError> {
     use naga::valid::Validator;
-    let module_info = Validator::new(
+    Ok(Validator::new(
         naga::valid::ValidationFlags::all(),
         naga::valid::Capabilities::all(),
     )
-    .validate(&module)?;
+    .validate(module)?)
+}
 
-    wgsl_writer.write(&module, &module_info)?;
+/// Validate the module and write it as WGSL
+fn write_wgsl(module: &naga::Module) -> Result<String, ShaderProcessingError> {
+    use naga::back::wgsl::WriterFlags;
+
+    let module_info = validate(module)?;
+
+    let mut wgsl = String::new();
+    let mut wgsl_writer = naga::back::wgsl::Writer::new(&mut wgsl, WriterFlags::empty());
+    wgsl_writer.write(module, &module_info)?;
 
     Ok(wgsl)
 }
 
-pub struct WgslShaderCode(String);
+/// Options for converting a ShaderToy shader
+#[derive(Debug, Default)]
+pub struct ShaderToyOptions {
+    /// Name of the render pass that defines the SDF, e.g. `Buffer A`, the image pass if `None`
+    pub pass: Option<String>,
+    /// Function name of the SDF function, e.g. `float sdf(vec3)`, detected if `None`
+    pub sdf: Option<String>,
+    /// Image files bound to the channels instead of the inputs of the render pass
+    pub channels: [Option<std::path::PathBuf>; CHANNEL_COUNT],
+}
 
-impl WgslShaderCode {
-    pub fn from_glsl(glsl: &str) -> Result<Self, ShaderProcessingError> {
-        convert_glsl_to_wgsl(glsl).map(Self)
+/// Client for the ShaderToy API.
+///
+/// Failed requests are retried if they timed out, the connection failed or the server responded
+/// with a server error or "too many requests".
+pub struct ShaderToyClient {
+    client: reqwest::Client,
+    api_url: String,
+    api_key: String,
+    media_url: String,
+    retries: u32,
+    cache: Option<ShaderCache>,
+}
+
+impl ShaderToyClient {
+    /// Client for `https://www.shadertoy.com/api/v1` with the default key, 3 retries and without cache
+    pub fn new() -> Result<Self, ShaderProcessingError> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            client,
+            api_url: DEFAULT_API_URL.into(),
+            api_key: DEFAULT_API_KEY.into(),
+            media_url: DEFAULT_MEDIA_URL.into(),
+            retries: 3,
+            cache: None,
+        })
     }
 
-    pub fn remove_function(&mut self, function_name: &str) -> Result<(), ShaderProcessingError> {
-        self.0 = remove_function_from_wgsl(&self.0, function_name)?;
-        Ok(())
+    /// Base URL of the API, e.g. of a mirror or a mock server
+    pub fn set_api_url(&mut self, api_url: &str) -> &mut Self {
+        self.api_url = api_url.trim_end_matches('/').into();
+        self
     }
 
-    pub fn has_function(&self, function_name: &str) -> bool {
-        wgsl_has_function(&self.0, function_name).unwrap_or(false)
+    pub fn set_api_key(&mut self, api_key: &str) -> &mut Self {
+        self.api_key = api_key.into();
+        self
     }
 
-    pub fn rename_function(
-        &mut self,
-        old_function_name: &str,
-        new_function_name: &str,
-    ) -> Result<(), ShaderProcessingError> {
-        self.0 = rename_function_in_wgsl(&self.0, old_function_name, new_function_name)?;
-        Ok(())
+    /// URL media files are fetched from, followed by the source of the input
+    pub fn set_media_url(&mut self, media_url: &str) -> &mut Self {
+        self.media_url = media_url.trim_end_matches('/').into();
+        self
     }
 
-    pub fn remove_line(&mut self, line_to_be_removed: &str) {
-        let mut s = String::new();
-        for line in self.0.lines() {
-            if line.trim() != line_to_be_removed.trim() {
-                s += line;
-                s += "\n";
-            }
+    /// Set the API URL and key from the environment variables
+    /// `SDF2MESH_SHADERTOY_API_URL` and `SDF2MESH_SHADERTOY_API_KEY`, if they are set
+    pub fn set_from_env(&mut self) -> &mut Self {
+        if let Ok(api_url) = std::env::var(API_URL_ENV) {
+            self.set_api_url(&api_url);
+        }
+        if let Ok(api_key) = std::env::var(API_KEY_ENV) {
+            self.set_api_key(&api_key);
         }
-        self.0 = s;
+        self
     }
 
-    pub fn add_line(&mut self, line: &str) {
-        self.0 += line;
-        self.0 += "\n";
+    /// Number of times a failed request is retried
+    pub fn set_retries(&mut self, retries: u32) -> &mut Self {
+        self.retries = retries;
+        self
     }
 
-    pub fn write_to_file(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
-        let mut f = std::io::BufWriter::new(std::fs::File::create(path)?);
-        use std::io::Write;
-        f.write_all(self.0.as_bytes())?;
-        Ok(())
+    /// Cache shaders are read from and fetched shaders are stored in
+    pub fn set_cache(&mut self, cache: Option<ShaderCache>) -> &mut Self {
+        self.cache = cache;
+        self
     }
-}
 
-impl std::fmt::Display for WgslShaderCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-fn remove_function_from_wgsl(
-    wgsl: &str,
-    function_name: &str,
-) -> Result<String, ShaderProcessingError> {
-    // find function name in wgsl
-    let lines = wgsl.lines();
-    let mut new_wgsl = String::new();
-    let mut in_function = false;
-    let mut function_found = false;
-    let mut curly_braces = 0;
-
-    for line in lines {
-        let line = line.trim();
-        if line.starts_with(function_name) {
-            in_function = true;
-            function_found = true;
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    /// Fetch a shader, from the cache if it contains the shader
+    pub async fn fetch_shader(&self, shader_id: &str) -> Result<Shader, ShaderProcessingError> {
+        if let Some(json) = self
+            .cache
+            .as_ref()
+            .map(|cache| cache.load(shader_id))
+            .transpose()?
+            .flatten()
+        {
+            log::info!("Read shader {shader_id} from cache");
+            return Shader::from_json(&json);
+        }
+
+        let json = self.get(&format!("shaders/{shader_id}")).await?;
+        let shader = Shader::from_json(&json)?;
+
+        if let Some(cache) = &self.cache {
+            if let Err(err) = cache.store(shader_id, &json) {
+                log::warn!("Could not store shader {shader_id} in cache: {err}");
+            }
+        }
+
+        Ok(shader)
+    }
+
+    /// Fetch a media file like a texture by the source of an input, from the cache if it contains the file
+    pub async fn fetch_media(&self, src: &str) -> Result<Vec<u8>, ShaderProcessingError> {
+        if let Some(bytes) = self
+            .cache
+            .as_ref()
+            .map(|cache| cache.load_media(src))
+            .transpose()?
+            .flatten()
+        {
+            return Ok(bytes);
+        }
+
+        log::info!("Fetching {src}");
+        let url = format!("{}/{}", self.media_url, src.trim_start_matches('/'));
+        let bytes = self.send(&url, &[]).await?.bytes().await?.to_vec();
+
+        if let Some(cache) = &self.cache {
+            if let Err(err) = cache.store_media(src, &bytes) {
+                log::warn!("Could not store {src} in cache: {err}");
+            }
         }
 
-        if in_function {
-            for c in line.chars() {
-                if c == '{' {
-                    curly_braces += 1;
-                } else if c == '}' {
-                    curly_braces -= 1;
+        Ok(bytes)
+    }
+
+    /// Load the images of the channels read by the render pass that defines the SDF.
+    ///
+    /// A channel reads the image file given in the options, or the texture or volume of its input.
+    /// The filter, wrap mode, vertical flip and sRGB settings are taken from the input.
+    pub async fn load_channels(
+        &self,
+        shader: &Shader,
+        options: &ShaderToyOptions,
+    ) -> Result<[Option<Channel>; CHANNEL_COUNT], ShaderProcessingError> {
+        let pass = shader.sdf_pass(options.pass.as_deref())?;
+        let bound = options.channels.each_ref().map(Option::is_some);
+        let code = shader.fetch_code(options.pass.as_deref(), &bound)?;
+
+        let mut channels: [Option<Channel>; CHANNEL_COUNT] = Default::default();
+        for (index, file) in options.channels.iter().enumerate() {
+            let name = format!("iChannel{index}");
+            if !code.contains(&name) {
+                continue;
+            }
+
+            let input = pass
+                .inputs
+                .iter()
+                .find(|input| input.channel == index as i32);
+            // ShaderToy flips images vertically by default
+            let vflip = input.is_none_or(|input| input.sampler.vflip == "true");
+            let image = match (file, input) {
+                (Some(file), _) => {
+                    let bytes = std::fs::read(file)?;
+                    channel::Image::decode(&file.display().to_string(), &bytes, vflip)?
+                }
+                (None, Some(input)) if matches!(input.ctype.as_str(), "texture" | "volume") => {
+                    let bytes = self.fetch_media(&input.src).await?;
+                    channel::Image::decode(&input.src, &bytes, vflip)?
                 }
+                (None, input) => {
+                    return Err(ShaderProcessingError::UnsupportedChannel {
+                        channel: index,
+                        input: input.map(|input| input.ctype.clone()),
+                    })
+                }
+            };
+
+            let mut channel = Channel::new(image);
+            if let Some(input) = input {
+                channel.filter = input.sampler.filter();
+                channel.wrap = input.sampler.wrap();
+                channel.srgb = input.sampler.srgb == "true";
             }
+            channels[index] = Some(channel);
         }
-        if curly_braces == 0 {
-            if !in_function {
-                new_wgsl += format!("{}\n", line).as_str();
+
+        Ok(channels)
+    }
+
+    /// Send a GET request for `path` relative to the API URL and return the response text
+    async fn get(&self, path: &str) -> Result<String, ShaderProcessingError> {
+        let url = format!("{}/{path}", self.api_url);
+        let response = self.send(&url, &[("key", &self.api_key)]).await?;
+        Ok(response.text().await?)
+    }
+
+    /// Send a GET request, retry it if it failed with a transient error
+    async fn send(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<reqwest::Response, ShaderProcessingError> {
+        let mut retry = 0;
+        loop {
+            let response = self
+                .client
+                .get(url)
+                .query(query)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                // The URL contains the API key in the query
+                .map_err(reqwest::Error::without_url);
+            match response {
+                Ok(response) => return Ok(response),
+                Err(err) if retry < self.retries && Self::is_transient(&err) => {
+                    let delay = RETRY_DELAY * 2u32.pow(retry);
+                    retry += 1;
+                    log::warn!("Request to {url} failed: {err}, retry {retry} in {delay:?}");
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => return Err(err.into()),
             }
-            in_function = false;
         }
     }
 
-    if !function_found {
-        return Err(ShaderProcessingError::ShaderError(format!(
-            "Function {} not found in shader",
-            function_name
-        )));
+    fn is_transient(err: &reqwest::Error) -> bool {
+        err.is_timeout()
+            || err.is_connect()
+            || err.status().is_some_and(|status| {
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            })
     }
+}
+
+/// Environment variable with the directory of the ShaderToy cache
+pub const CACHE_DIR_ENV: &str = "SDF2MESH_SHADERTOY_CACHE";
 
-    Ok(new_wgsl)
+/// On-disk cache of shaders fetched from the ShaderToy API, one JSON file per shader ID.
+/// Media files like textures are stored in the subdirectory `media`.
+pub struct ShaderCache {
+    dir: std::path::PathBuf,
 }
 
-fn wgsl_has_function(wgsl: &str, function_name: &str) -> Result<bool, ShaderProcessingError> {
-    let lines = wgsl.lines();
-    let mut function_found = false;
-    for line in lines {
-        let line = line.trim();
-        if line.starts_with(format!("fn {function_name}(").as_str()) {
-            function_found = true;
-            break;
+impl ShaderCache {
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Cache in the directory given by `SDF2MESH_SHADERTOY_CACHE` or in the user's cache directory,
+    /// e.g. `~/.cache/sdf2mesh/shadertoy`
+    pub fn from_env() -> Option<Self> {
+        if let Some(dir) = std::env::var_os(CACHE_DIR_ENV) {
+            return Some(Self::new(dir));
         }
+        let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+            .map(std::path::PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".cache"))
+            })
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(std::path::PathBuf::from))?;
+        Some(Self::new(cache_dir.join("sdf2mesh").join("shadertoy")))
+    }
+
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
     }
 
-    if !function_found {
-        return Err(ShaderProcessingError::ShaderError(format!(
-            "Function {} not found in shader",
-            function_name
-        )));
-    }
-
-    Ok(true)
-}
-
-fn rename_function_in_wgsl(
-    wgsl: &str,
-    old_function_name: &str,
-    new_function_name: &str,
-) -> Result<String, ShaderProcessingError> {
-    // find function name in wgsl
-    let lines = wgsl.lines();
-    let mut new_wgsl = String::new();
-    let mut in_function = false;
-    let mut function_found = false;
-    for line in lines {
-        let line = line.trim();
-        if line.starts_with(format!("fn {old_function_name}(").as_str()) {
-            in_function = true;
-            function_found = true;
-            new_wgsl += line
-                .replacen(old_function_name, new_function_name, 1)
-                .as_str();
-        } else {
-            new_wgsl += format!("{}\n", line).as_str();
+    /// Path of the cached JSON file of a shader
+    fn path(&self, shader_id: &str) -> Result<std::path::PathBuf, ShaderProcessingError> {
+        if shader_id.is_empty() || !shader_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("Invalid ShaderToy shader ID `{shader_id}`").into());
         }
+        Ok(self.dir.join(format!("{shader_id}.json")))
+    }
 
-        if in_function && line.starts_with('}') {
-            in_function = false;
+    /// Path of a cached media file by the source of an input, e.g. `/media/a/<hash>.png`
+    fn media_path(&self, src: &str) -> Result<std::path::PathBuf, ShaderProcessingError> {
+        let name = src.rsplit('/').next().unwrap_or_default();
+        if name.starts_with('.')
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        {
+            return Err(format!("Invalid ShaderToy media file `{src}`").into());
         }
+        Ok(self.dir.join("media").join(name))
+    }
+
+    /// Read the JSON of a shader, `None` if the shader is not cached
+    pub fn load(&self, shader_id: &str) -> Result<Option<String>, ShaderProcessingError> {
+        Ok(read_if_exists(&self.path(shader_id)?)?
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
     }
 
-    if !function_found {
-        return Err(ShaderProcessingError::ShaderError(format!(
-            "Function `{}` not found in shader",
-            old_function_name
-        )));
+    /// Write the JSON of a shader to the cache
+    pub fn store(&self, shader_id: &str, json: &str) -> Result<(), ShaderProcessingError> {
+        write_creating_dir(&self.path(shader_id)?, json.as_bytes())
     }
 
-    Ok(new_wgsl)
+    /// Read a media file, `None` if the file is not cached
+    pub fn load_media(&self, src: &str) -> Result<Option<Vec<u8>>, ShaderProcessingError> {
+        Ok(read_if_exists(&self.media_path(src)?)?)
+    }
+
+    /// Write a media file to the cache
+    pub fn store_media(&self, src: &str, bytes: &[u8]) -> Result<(), ShaderProcessingError> {
+        write_creating_dir(&self.media_path(src)?, bytes)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn remove_function() {
-        let wgsl = r#"        
-fn mainImage(fragColor: ptr<function, vec4<f32>>, fragCoord: vec2<f32>) {
-    var fragCoord_1: vec2<f32>;
+/// Read a file, `None` if it does not exist
+fn read_if_exists(path: &std::path::Path) -> std::io::Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
 
-    fragCoord_1 = fragCoord;
-    return;
+/// Write a file, create its directory if needed
+fn write_creating_dir(path: &std::path::Path, bytes: &[u8]) -> Result<(), ShaderProcessingError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, bytes)?;
+    Ok(())
 }
 
-fn main_1() {
-    return;
+pub fn convert_glsl_to_wgsl(glsl: &str) -> Result<String, ShaderProcessingError> {
+    write_wgsl(&parse_glsl(glsl)?)
 }
 
-@fragment
-fn main() {
-    main_1();
-    return;
+/// Names of SDF functions that are picked when there are several candidates, in order of preference
+const SDF_FUNCTION_NAMES: &[&str] = &["sdf3d", "sdf", "map", "de", "scene"];
+
+/// WGSL shader code converted from GLSL.
+///
+/// Functions are removed and renamed in the naga module, which is written as WGSL by `to_wgsl`.
+/// Functions are referred to by their GLSL name.
+pub struct WgslShaderCode {
+    module: naga::Module,
+
+    /// WGSL code appended to the code written from the module
+    code: String,
 }
-"#;
 
-        let new_wgsl = remove_function_from_wgsl(wgsl, "fn main_1()").unwrap();
+impl WgslShaderCode {
+    pub fn from_glsl(glsl: &str) -> Result<Self, ShaderProcessingError> {
+        Ok(Self {
+            module: parse_glsl(glsl)?,
+            code: String::new(),
+        })
+    }
 
-        assert!(new_wgsl.contains("fn mainImage(fragColor"));
-        assert!(!new_wgsl.contains("fn main_1()"));
+    fn function(&self, function_name: &str) -> Option<naga::Handle<naga::Function>> {
+        self.module
+            .functions
+            .fetch_if(|function| function.name.as_deref() == Some(function_name))
+    }
 
-        let new_wgsl = remove_function_from_wgsl(&new_wgsl, "fn main(").unwrap();
-        assert!(new_wgsl.contains("fn mainImage(fragColor"));
-        println!("{}", new_wgsl);
+    fn function_not_found(function_name: &str) -> ShaderProcessingError {
+        ShaderProcessingError::ShaderError(format!(
+            "Function `{function_name}` not found in shader"
+        ))
+    }
 
-        assert!(!new_wgsl.contains("fn main()"));
+    /// Remove all entry points, e.g. the fragment shader `main`
+    pub fn remove_entry_points(&mut self) {
+        self.module.entry_points.clear();
+    }
 
-        let new_wgsl = remove_function_from_wgsl(&new_wgsl, "fn mainImage(").unwrap();
+    /// Remove a function, which must not be called by other functions or entry points
+    pub fn remove_function(&mut self, function_name: &str) -> Result<(), ShaderProcessingError> {
+        let removed = self
+            .function(function_name)
+            .ok_or_else(|| Self::function_not_found(function_name))?;
+
+        let mut module = self.module.clone();
+        let mut handles = naga::FastHashMap::default();
+        let mut functions = naga::Arena::new();
+        for (handle, function, span) in module.functions.drain() {
+            if handle != removed {
+                handles.insert(handle, functions.append(function, span));
+            }
+        }
+        module.functions = functions;
+
+        let callers = module
+            .functions
+            .iter_mut()
+            .map(|(_, function)| function)
+            .chain(module.entry_points.iter_mut().map(|ep| &mut ep.function));
+        for caller in callers {
+            let mut is_caller = false;
+            for_each_call(caller, &mut |function| match handles.get(function) {
+                Some(handle) => *function = *handle,
+                None => is_caller = true,
+            });
+            if is_caller {
+                return Err(ShaderProcessingError::ShaderError(format!(
+                    "Function `{function_name}` cannot be removed, it is called by `{}`",
+                    caller.name.as_deref().unwrap_or_default()
+                )));
+            }
+        }
+
+        self.module = module;
+        Ok(())
+    }
 
-        assert_eq!(new_wgsl.trim(), "@fragment");
+    pub fn has_function(&self, function_name: &str) -> bool {
+        self.function(function_name).is_some()
     }
 
+    /// Rename a function, calls refer to the function by handle and call the renamed function
+    pub fn rename_function(
+        &mut self,
+        old_function_name: &str,
+        new_function_name: &str,
+    ) -> Result<(), ShaderProcessingError> {
+        let handle = self
+            .function(old_function_name)
+            .ok_or_else(|| Self::function_not_found(old_function_name))?;
+        self.module.functions.get_mut(handle).name = Some(new_function_name.into());
+        Ok(())
+    }
+
+    /// Name of a function in the WGSL code.
+    ///
+    /// The WGSL writer renames functions, e.g. `main` becomes `main_1` if there is
+    /// an entry point `main`, and `sdf2` becomes `sdf2_`.
+    pub fn wgsl_function_name(&self, function_name: &str) -> Result<String, ShaderProcessingError> {
+        let handle = self
+            .function(function_name)
+            .ok_or_else(|| Self::function_not_found(function_name))?;
+
+        let mut names = naga::FastHashMap::default();
+        naga::proc::Namer::default().reset(
+            &self.module,
+            naga::keywords::wgsl::RESERVED,
+            &[],
+            &[],
+            &["__", "_naga"],
+            &mut names,
+        );
+        Ok(names[&naga::proc::NameKey::Function(handle)].clone())
+    }
+
+    /// Names of the functions with the signature `float(vec3)`
+    pub fn sdf_function_candidates(&self) -> Vec<String> {
+        crate::shader::sdf_function_candidates(&self.module)
+    }
+
+    /// Find the SDF function named `sdf` or detect it if `sdf` is `None`.
+    ///
+    /// A function is detected if it is the only candidate with the signature `float(vec3)`
+    /// or if it has one of the names `sdf3d`, `sdf`, `map`, `de` or `scene`.
+    pub fn find_sdf_function(&self, sdf: Option<&str>) -> Result<String, ShaderProcessingError> {
+        if let Some(sdf) = sdf {
+            return match self.has_function(sdf) {
+                true => Ok(sdf.into()),
+                false => Err(ShaderProcessingError::MissingSdf(sdf.into())),
+            };
+        }
+
+        let candidates = self.sdf_function_candidates();
+        match candidates.as_slice() {
+            [] => return Err(ShaderProcessingError::NoSdfCandidate),
+            [candidate] => return Ok(candidate.clone()),
+            _ => {}
+        }
+        SDF_FUNCTION_NAMES
+            .iter()
+            .find(|name| candidates.iter().any(|candidate| candidate == *name))
+            .map(|name| name.to_string())
+            .ok_or(ShaderProcessingError::AmbiguousSdf(candidates))
+    }
+
+    /// Sample textures at mip level 0 instead of the level computed from implicit derivatives,
+    /// which are only available in fragment shaders
+    pub fn sample_level_zero(&mut self) {
+        let functions = self
+            .module
+            .functions
+            .iter_mut()
+            .map(|(_, function)| function)
+            .chain(
+                self.module
+                    .entry_points
+                    .iter_mut()
+                    .map(|ep| &mut ep.function),
+            );
+        for function in functions {
+            for (_, expression) in function.expressions.iter_mut() {
+                if let naga::Expression::ImageSample { level, .. } = expression {
+                    if matches!(level, naga::SampleLevel::Auto | naga::SampleLevel::Bias(_)) {
+                        *level = naga::SampleLevel::Zero;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Append WGSL code to the code written from the module
+    pub fn add_line(&mut self, line: &str) {
+        self.code += line;
+        self.code += "\n";
+    }
+
+    /// Write the module as WGSL, followed by the added lines
+    pub fn to_wgsl(&self) -> Result<String, ShaderProcessingError> {
+        Ok(write_wgsl(&self.module)? + &self.code)
+    }
+
+    pub fn write_to_file(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), ShaderProcessingError> {
+        std::fs::write(path, self.to_wgsl()?)?;
+        Ok(())
+    }
+}
+
+/// Call `f` for the function handle of each call in `function`
+fn for_each_call(
+    function: &mut naga::Function,
+    f: &mut impl FnMut(&mut naga::Handle<naga::Function>),
+) {
+    for (_, expression) in function.expressions.iter_mut() {
+        if let naga::Expression::CallResult(handle) = expression {
+            f(handle);
+        }
+    }
+    for_each_call_in_block(&mut function.body, f);
+}
+
+fn for_each_call_in_block(
+    block: &mut naga::Block,
+    f: &mut impl FnMut(&mut naga::Handle<naga::Function>),
+) {
+    use naga::Statement;
+
+    for (statement, _) in block.span_iter_mut() {
+        match statement {
+            Statement::Block(block) => for_each_call_in_block(block, f),
+            Statement::If { accept, reject, .. } => {
+                for_each_call_in_block(accept, f);
+                for_each_call_in_block(reject, f);
+            }
+            Statement::Switch { cases, .. } => {
+                for case in cases {
+                    for_each_call_in_block(&mut case.body, f);
+                }
+            }
+            Statement::Loop {
+                body, continuing, ..
+            } => {
+                for_each_call_in_block(body, f);
+                for_each_call_in_block(continuing, f);
+            }
+            Statement::Call { function, .. } => f(function),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    /// GLSL shader with a function whose name is a prefix of another one and braces in a comment
+    const GLSL: &str = r#"#version 450 core
+float sdf_sphere(vec3 p) { return length(p) - 1.0; } // {
+float sdf(vec3 p) {
+    return sdf_sphere(p);
+}
+vec3 normal(
+    vec3 p,
+    float epsilon
+) {
+    return vec3(sdf(p + epsilon), sdf(p), sdf(p - epsilon));
+}
+void main() {}
+"#;
+
     #[test]
+    fn remove_function() {
+        let mut wgsl = WgslShaderCode::from_glsl(GLSL).unwrap();
+
+        // `main` is called by the fragment entry point
+        assert!(wgsl.remove_function("main").is_err());
+        wgsl.remove_entry_points();
+        wgsl.remove_function("main").unwrap();
+        assert!(!wgsl.has_function("main"));
+        assert!(wgsl.remove_function("main").is_err());
+
+        // `sdf` is called by `normal`
+        assert!(wgsl.remove_function("sdf").is_err());
+        wgsl.remove_function("normal").unwrap();
+        wgsl.remove_function("sdf").unwrap();
+        assert!(wgsl.has_function("sdf_sphere"));
+
+        let code = wgsl.to_wgsl().unwrap();
+        assert!(code.contains("fn sdf_sphere("));
+        assert!(!code.contains("fn sdf("));
+        assert!(!code.contains("@fragment"));
+    }
 
+    #[test]
     fn rename_function() {
-        let in_wgsl = r#"fn normal(p_4: vec3<f32>, epsilon: f32) -> vec3<f32>"#;
-        let out_wgsl = rename_function_in_wgsl(in_wgsl, "normal", "sdf3d_normal").unwrap();
+        let mut wgsl = WgslShaderCode::from_glsl(GLSL).unwrap();
+        wgsl.remove_entry_points();
+        wgsl.remove_function("main").unwrap();
+        wgsl.rename_function("normal", "sdf3d_normal").unwrap();
+        wgsl.rename_function("sdf", "body").unwrap();
+        assert!(wgsl.rename_function("sdf", "body").is_err());
+        wgsl.add_line("const ONE = 1.0;");
+
+        let code = wgsl.to_wgsl().unwrap();
+        assert!(code.contains("fn sdf3d_normal(p_4: vec3<f32>, epsilon: f32) -> vec3<f32> {\n"));
+        assert!(code.contains("fn body(p_2: vec3<f32>) -> f32 {\n"));
+        assert!(code.contains("let _e12 = body((_e8 + vec3(_e9)));"));
+        assert!(!code.contains("sdf(") && !code.contains("fn normal("));
+        assert!(code.ends_with("}\n\nconst ONE = 1.0;\n"));
+    }
 
-        assert!(out_wgsl.contains("fn sdf3d_normal(p_4: vec3<f32>, epsilon: f32) -> vec3<f32>"));
+    #[test]
+    fn from_json() {
+        let json = r#"{"ver": "0.1", "info": {"id": "abc123", "date": "0", "viewed": 0, "name": "Test",
+            "username": "user", "description": "", "likes": 0, "published": 3, "flags": 0,
+            "usePreview": 0, "tags": [], "hasliked": 0}, "renderpass": []}"#;
+        assert_eq!(Shader::from_json(json).unwrap().info.id, "abc123");
+        let response = format!(r#"{{"Shader": {json}}}"#);
+        assert_eq!(Shader::from_json(&response).unwrap().info.name, "Test");
+
+        assert!(matches!(
+            Shader::from_json(r#"{"Error": "Shader not found"}"#),
+            Err(ShaderProcessingError::ShaderError(error)) if error == "Shader not found"
+        ));
+        assert!(matches!(
+            Shader::from_json("{}"),
+            Err(ShaderProcessingError::JsonError(_))
+        ));
+    }
+
+    /// Serve `responses` on a local port, one per connection, and return the URL and the request lines
+    fn mock_server(responses: Vec<String>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{BufRead, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/v1/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                requests.push(line.trim().to_string());
+                while line.trim() != "" {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn client() {
+        let json = r#"{"Shader": {"ver": "0.1", "info": {"id": "abc123", "date": "0", "viewed": 0,
+            "name": "Test", "username": "user", "description": "", "likes": 0, "published": 3,
+            "flags": 0, "usePreview": 0, "tags": [], "hasliked": 0}, "renderpass": []}}"#;
+        let (url, server) = mock_server(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .into(),
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{json}",
+                json.len()
+            ),
+        ]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut client = ShaderToyClient::new().unwrap();
+        client
+            .set_api_url(&url)
+            .set_api_key("secret")
+            .set_retries(1)
+            .set_cache(Some(ShaderCache::new(dir.path())));
+        assert_eq!(client.api_url(), url.trim_end_matches('/'));
+
+        let shader = client.fetch_shader("abc123").await.unwrap();
+        assert_eq!(shader.info.name, "Test");
+        assert_eq!(
+            server.join().unwrap(),
+            ["GET /api/v1/shaders/abc123?key=secret HTTP/1.1"; 2]
+        );
+
+        // The shader is read from the cache now, the server is gone
+        assert_eq!(
+            client.fetch_shader("abc123").await.unwrap().info.id,
+            "abc123"
+        );
+        client.set_cache(None).set_retries(0);
+        let err = client.fetch_shader("abc123").await.unwrap_err();
+        assert!(matches!(err, ShaderProcessingError::RequestError(_)));
+        assert!(!err.to_string().contains("secret"));
+
+        // Errors of the server do not reveal the API key either
+        let (url, server) = mock_server(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .into(),
+        ]);
+        client.set_api_url(&url);
+        let err = client.fetch_shader("abc123").await.unwrap_err();
+        server.join().unwrap();
+        assert!(err.to_string().contains("503"));
+        assert!(!err.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn multipass() {
+        let pass = |name: &str, r#type: &str, code: &str| RenderPass {
+            name: name.into(),
+            r#type: r#type.into(),
+            code: code.into(),
+            ..Default::default()
+        };
+        let mut image = pass(
+            "Image",
+            "image",
+            "float map(vec3 p) { return sphere(p); }\nvoid mainImage(out vec4 c, in vec2 f) { c = texture(iChannel0, f); }",
+        );
+        let mut buffer = pass(
+            "Buffer A",
+            "buffer",
+            "float map(vec3 p) { return sphere(p) - 0.1; }\nvoid mainImage(out vec4 c, in vec2 f) {}",
+        );
+        buffer.outputs.push(ShaderOutput {
+            id: 257,
+            channel: 0,
+        });
+        let common = pass(
+            "Common",
+            "common",
+            "float sphere(vec3 p) { return length(p) - 1.0; }",
+        );
+        let sound = pass(
+            "Sound",
+            "sound",
+            "vec2 mainSound(int s, float t) { return vec2(0.0); }",
+        );
+        let shader = Shader {
+            renderpass: vec![buffer, image, common, sound],
+            ..Default::default()
+        };
+
+        // The common code comes first, other passes are ignored
+        let code = shader.fetch_code(None, &[]).unwrap();
+        assert!(code.starts_with("float sphere("));
+        assert!(code.contains("return sphere(p); }"));
+        assert!(!code.contains("sphere(p) - 0.1") && !code.contains("mainSound"));
+        let code = shader.fetch_code(Some("buffer a"), &[]).unwrap();
+        assert!(code.contains("sphere(p) - 0.1") && !code.contains("return sphere(p); }"));
+        assert!(matches!(
+            shader.fetch_code(Some("Buffer B"), &[]),
+            Err(ShaderProcessingError::ShaderError(error)) if error.contains("`Buffer A`, `Image`, `Common`, `Sound`")
+        ));
+
+        // The image pass reads the output of buffer A
+        let mut shader = shader;
+        image = shader.renderpass.remove(1);
+        image.inputs.push(ShaderInput {
+            id: 257,
+            ctype: "buffer".into(),
+            channel: 0,
+            ..Default::default()
+        });
+        shader.renderpass.push(image);
+        let err = shader.fetch_code(None, &[]).unwrap_err();
+        assert!(matches!(
+            &err,
+            ShaderProcessingError::BufferInput { pass, buffer, channel: 0 } if pass == "Image" && buffer == "Buffer A"
+        ));
+        assert!(err
+            .to_string()
+            .contains("reads `Buffer A` from `iChannel0`"));
+        assert!(shader
+            .generate_wgsl_shader_code(Some("Buffer A"), &[])
+            .is_ok());
+
+        // An image bound to the channel replaces the buffer
+        assert!(shader.fetch_code(None, &[true]).is_ok());
+        assert!(shader.fetch_code(None, &[false, true]).is_err());
+    }
+
+    #[tokio::test]
+    async fn channels() {
+        let dir = tempfile::tempdir().unwrap();
+        let noise = dir.path().join("noise.png");
+        crate::png::image_data_to_file([10, 20, 30, 255].repeat(8), (4, 2), &noise);
+        let cache = ShaderCache::new(dir.path().join("shadertoy"));
+        cache
+            .store_media("/media/a/noise.png", &std::fs::read(&noise).unwrap())
+            .unwrap();
+        assert!(cache.store_media("/media/a/..", &[]).is_err());
+
+        let mut image = RenderPass {
+            name: "Image".into(),
+            r#type: "image".into(),
+            code: "float map(vec3 p) { return length(p) - texture(iChannel0, p.xy).x; }".into(),
+            ..Default::default()
+        };
+        image.inputs.push(ShaderInput {
+            src: "/media/a/noise.png".into(),
+            ctype: "texture".into(),
+            channel: 0,
+            sampler: Sampler {
+                filter: "nearest".into(),
+                wrap: "clamp".into(),
+                vflip: "true".into(),
+                srgb: "true".into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let shader = Shader {
+            renderpass: vec![image],
+            ..Default::default()
+        };
+
+        // The media of the input is read from the cache, with the settings of its sampler
+        let mut client = ShaderToyClient::new().unwrap();
+        client
+            .set_media_url("http://localhost:9/unreachable")
+            .set_retries(0)
+            .set_cache(Some(cache));
+        let mut options = ShaderToyOptions::default();
+        let channels = client.load_channels(&shader, &options).await.unwrap();
+        let channel = channels[0].as_ref().unwrap();
+        assert_eq!(channel.image.size, [4, 2, 1]);
+        assert_eq!(channel.filter, channel::Filter::Nearest);
+        assert_eq!(channel.wrap, channel::Wrap::Clamp);
+        assert!(channel.srgb);
+        assert!(channels[1..].iter().all(Option::is_none));
+
+        // A local image replaces the input
+        crate::png::image_data_to_file(vec![0; 4], (1, 1), &noise);
+        options.channels[0] = Some(noise);
+        let channels = client.load_channels(&shader, &options).await.unwrap();
+        assert_eq!(channels[0].as_ref().unwrap().image.size, [1, 1, 1]);
+
+        // Channels without input need a local image
+        let mut shader = shader;
+        shader.renderpass[0].code =
+            "float map(vec3 p) { return texture(iChannel1, p.xy).x; }".into();
+        assert!(matches!(
+            client.load_channels(&shader, &options).await,
+            Err(ShaderProcessingError::UnsupportedChannel {
+                channel: 1,
+                input: None
+            })
+        ));
+
+        // A local image replaces the output of a buffer
+        let input = &mut shader.renderpass[0].inputs[0];
+        input.ctype = "buffer".into();
+        input.channel = 1;
+        assert!(matches!(
+            client.load_channels(&shader, &options).await,
+            Err(ShaderProcessingError::BufferInput { channel: 1, .. })
+        ));
+        options.channels.swap(0, 1);
+        let channels = client.load_channels(&shader, &options).await.unwrap();
+        assert_eq!(channels[1].as_ref().unwrap().image.size, [1, 1, 1]);
+    }
+
+    #[test]
+    fn cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ShaderCache::new(dir.path().join("shadertoy"));
+        assert!(cache.load("abc123").unwrap().is_none());
+        cache.store("abc123", "{}").unwrap();
+        assert_eq!(cache.load("abc123").unwrap().unwrap(), "{}");
+        assert!(dir.path().join("shadertoy/abc123.json").exists());
+        assert!(cache.store("../abc", "{}").is_err());
+    }
+
+    #[test]
+    fn wgsl_function_name() {
+        let mut wgsl = WgslShaderCode::from_glsl(
+            "#version 450 core\nfloat sdf2(vec3 p) { return p.x; }\nvoid main() {}",
+        )
+        .unwrap();
+        assert_eq!(wgsl.sdf_function_candidates(), ["sdf2"]);
+        assert_eq!(wgsl.wgsl_function_name("sdf2").unwrap(), "sdf2_");
+        assert_eq!(wgsl.wgsl_function_name("main").unwrap(), "main_1");
+        wgsl.remove_entry_points();
+        assert_eq!(wgsl.wgsl_function_name("main").unwrap(), "main");
+        assert!(wgsl
+            .to_wgsl()
+            .unwrap()
+            .contains("fn sdf2_(p: vec3<f32>) -> f32"));
     }
 
     #[test]
@@ -0,0 +1,104 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::path::PathBuf;
+
+/// Origin of lines in a composed shader source
+#[derive(Clone, Debug, PartialEq)]
+pub enum SourceOrigin {
+    /// A source file, e.g. the user's `.sdf3d` file or an included file
+    File(PathBuf),
+    /// A registered module, e.g. `sdf::op`
+    Module(String),
+    /// Source added under a name, e.g. `dualcontour.wgsl`
+    Named(String),
+}
+
+impl std::fmt::Display for SourceOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Module(name) => write!(f, "<module {name}>"),
+            Self::Named(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Maps each line of a composed shader source back to its origin and line number
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    /// Origins of the source lines
+    origins: Vec<SourceOrigin>,
+    /// Index of the origin and line number (starting at 1) for each line of the composed source
+    lines: Vec<(usize, usize)>,
+}
+
+impl SourceMap {
+    /// Append a single `line` with its `line_number` in `origin` to `source`
+    pub fn push_line(
+        &mut self,
+        source: &mut String,
+        line: &str,
+        origin: &SourceOrigin,
+        line_number: usize,
+    ) {
+        if self.origins.last() != Some(origin) {
+            self.origins.push(origin.clone());
+        }
+
+        source.push_str(line);
+        source.push('\n');
+        self.lines.push((self.origins.len() - 1, line_number));
+    }
+
+    /// Append all lines of `text` to `source`, `text` starts at line 1 of `origin`
+    pub fn append(&mut self, source: &mut String, text: &str, origin: &SourceOrigin) {
+        for (line_idx, line) in text.lines().enumerate() {
+            self.push_line(source, line, origin, line_idx + 1);
+        }
+    }
+
+    /// Number of mapped lines
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Origin and line number of `line` (starting at 1) of the composed source
+    pub fn location(&self, line: usize) -> Option<(&SourceOrigin, usize)> {
+        let (origin_idx, line_number) = self.lines.get(line.checked_sub(1)?)?;
+        Some((&self.origins[*origin_idx], *line_number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locations() {
+        let file = SourceOrigin::File("a.sdf3d".into());
+        let module = SourceOrigin::Module("sdf::op".into());
+
+        let mut source = String::new();
+        let mut source_map = SourceMap::default();
+        source_map.push_line(&mut source, "use sdf::op;", &file, 1);
+        source_map.append(&mut source, "fn a() {}\nfn b() {}", &module);
+        source_map.push_line(&mut source, "fn sdf3d() {}", &file, 3);
+
+        assert_eq!(
+            source,
+            "use sdf::op;\nfn a() {}\nfn b() {}\nfn sdf3d() {}\n"
+        );
+        assert_eq!(source_map.len(), 4);
+        assert_eq!(source_map.location(0), None);
+        assert_eq!(source_map.location(1), Some((&file, 1)));
+        assert_eq!(source_map.location(3), Some((&module, 2)));
+        assert_eq!(source_map.location(4), Some((&file, 3)));
+        assert_eq!(source_map.location(5), None);
+    }
+}
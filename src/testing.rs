@@ -0,0 +1,181 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Helpers for tests that compare WGSL functions with their CPU implementations
+
+use crate::Vec3D;
+use wgpu::util::DeviceExt;
+
+/// Deterministic pseudo random points in a cube from `-extent` to `extent`
+pub fn sample_points(count: usize, extent: f32) -> Vec<Vec3D> {
+    let mut state = 0x2545_f491_u32;
+    let mut random = move || {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0
+    };
+
+    (0..count)
+        .map(|_| Vec3D::new(random(), random(), random()) * extent)
+        .collect()
+}
+
+/// Environment variable to skip GPU comparisons on machines without a usable GPU adapter
+pub const SKIP_GPU_TESTS_ENV: &str = "SDF2MESH_SKIP_GPU_TESTS";
+
+/// Report that there is no GPU to compare with.
+/// Panics unless GPU comparisons are skipped explicitly with `SDF2MESH_SKIP_GPU_TESTS`.
+fn skip_gpu_comparison(reason: &str) -> Option<Vec<Vec<f32>>> {
+    if std::env::var_os(SKIP_GPU_TESTS_ENV).is_none() {
+        panic!("{reason}. Set {SKIP_GPU_TESTS_ENV}=1 to skip GPU comparisons");
+    }
+    log::warn!("{reason}, skipping GPU comparison");
+    None
+}
+
+/// Evaluate the WGSL expression of each pair at all `points` on the GPU and assert that it matches
+/// the CPU function of the pair. `source` contains the functions used by the expressions,
+/// the position is passed as `p`. The difference may be `tolerance * (1 + |cpu|)`.
+pub fn assert_gpu_matches<E: AsRef<str>, F: Fn(Vec3D) -> f32>(
+    source: &str,
+    functions: &[(E, F)],
+    points: &[Vec3D],
+    tolerance: f32,
+) {
+    let expressions: Vec<&str> = functions.iter().map(|(e, _)| e.as_ref()).collect();
+    let Some(values) = evaluate_wgsl(source, &expressions, points) else {
+        return;
+    };
+
+    for ((expression, f), values) in functions.iter().zip(values) {
+        for (p, gpu) in points.iter().zip(values) {
+            let cpu = f(*p);
+            assert!(
+                (cpu - gpu).abs() <= tolerance * (1.0 + cpu.abs()),
+                "{} at {p:?}: CPU {cpu}, GPU {gpu}",
+                expression.as_ref()
+            );
+        }
+    }
+}
+
+/// Evaluate WGSL `expressions` of the position `p` at all `points` on the GPU.
+/// `source` contains the functions used by the expressions.
+/// Returns the values per expression, or `None` if there is no GPU and `SDF2MESH_SKIP_GPU_TESTS` is set.
+fn evaluate_wgsl(source: &str, expressions: &[&str], points: &[Vec3D]) -> Option<Vec<Vec<f32>>> {
+    pollster::block_on(evaluate_wgsl_async(source, expressions, points))
+}
+
+async fn evaluate_wgsl_async(
+    source: &str,
+    expressions: &[&str],
+    points: &[Vec3D],
+) -> Option<Vec<Vec<f32>>> {
+    let instance = wgpu::Instance::default();
+    let Some(adapter) = instance
+        .request_adapter(&wgpu::RequestAdapterOptions::default())
+        .await
+    else {
+        return skip_gpu_comparison("No GPU adapter available");
+    };
+    let (device, queue) = match adapter
+        .request_device(&wgpu::DeviceDescriptor::default(), None)
+        .await
+    {
+        Ok(device) => device,
+        Err(err) => return skip_gpu_comparison(&format!("Cannot create a GPU device: {err}")),
+    };
+
+    let n = expressions.len();
+    let assignments: String = expressions
+        .iter()
+        .enumerate()
+        .map(|(k, expression)| format!("    values[id.x * {n}u + {k}u] = {expression};\n"))
+        .collect();
+    let shader_source = format!(
+        r#"{source}
+
+@group(0) @binding(0) var<storage, read> points: array<vec4f>;
+@group(0) @binding(1) var<storage, read_write> values: array<f32>;
+
+@compute @workgroup_size(64)
+fn evaluate(@builtin(global_invocation_id) id: vec3u) {{
+    if id.x >= arrayLength(&points) {{
+        return;
+    }}
+    let p = points[id.x].xyz;
+{assignments}}}
+"#
+    );
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+    });
+
+    let point_data: Vec<[f32; 4]> = points.iter().map(|p| [p.x, p.y, p.z, 0.0]).collect();
+    let point_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&point_data),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let size = (points.len() * n * std::mem::size_of::<f32>()) as u64;
+    let value_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: None,
+        layout: None,
+        module: &shader,
+        entry_point: Some("evaluate"),
+        compilation_options: Default::default(),
+        cache: None,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: point_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: value_buffer.as_entire_binding(),
+            },
+        ],
+    });
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(points.len().div_ceil(64) as u32, 1, 1);
+    }
+    encoder.copy_buffer_to_buffer(&value_buffer, 0, &staging_buffer, 0, size);
+    queue.submit(Some(encoder.finish()));
+
+    let buffer_slice = staging_buffer.slice(..);
+    let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |r| sender.send(r).unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    receiver.receive().await?.ok()?;
+    let values: Vec<f32> = bytemuck::cast_slice(&buffer_slice.get_mapped_range()).to_vec();
+
+    Some(
+        (0..n)
+            .map(|k| values.iter().skip(k).step_by(n).copied().collect())
+            .collect(),
+    )
+}
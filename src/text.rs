@@ -0,0 +1,290 @@
+// Copyright © Michael Winkelmann <michael@winkelmann.site>
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Text parameters of SDF files, rendered with a bundled single-stroke font.
+//!
+//! A text is a string constant annotated with `@text`, optionally with a description:
+//!
+//! ```wgsl
+//! @text const LABEL = "SDF2MESH"; // Label on the front
+//! ```
+//!
+//! WGSL has no strings, so the text is laid out on the CPU and the strokes of its glyphs are generated into the shader.
+//! The constant becomes a handle `vec2u` to the strokes which is passed to the functions of the module `sdf3d::text`,
+//! e.g. `sdf3d_text_extrude(p, LABEL, 0.2, 0.03, 0.05)`.
+//! Like parameters, texts can be overridden before the shader is compiled.
+
+/// Width of the glyph grid
+const GLYPH_WIDTH: f32 = 4.0;
+
+/// Height of capital letters in the glyph grid
+const GLYPH_HEIGHT: f32 = 6.0;
+
+/// Distance between the glyphs in the glyph grid
+const GLYPH_SPACING: f32 = 2.0;
+
+/// Single-stroke glyphs on a 4x6 grid with the baseline at 0.
+/// Each glyph is a list of polylines separated by `|`, a polyline with a single repeated point is a dot.
+const GLYPHS: &[(char, &str)] = &[
+    (' ', ""),
+    ('A', "0,0 0,4 2,6 4,4 4,0|0,3 4,3"),
+    ('B', "0,0 0,6 3,6 4,5 4,4 3,3 0,3|3,3 4,2 4,1 3,0 0,0"),
+    ('C', "4,5 3,6 1,6 0,5 0,1 1,0 3,0 4,1"),
+    ('D', "0,0 0,6 2,6 4,4 4,2 2,0 0,0"),
+    ('E', "4,6 0,6 0,0 4,0|0,3 3,3"),
+    ('F', "4,6 0,6 0,0|0,3 3,3"),
+    ('G', "4,5 3,6 1,6 0,5 0,1 1,0 3,0 4,1 4,3 2,3"),
+    ('H', "0,0 0,6|4,0 4,6|0,3 4,3"),
+    ('I', "1,6 3,6|2,6 2,0|1,0 3,0"),
+    ('J', "4,6 4,1 3,0 1,0 0,1"),
+    ('K', "0,0 0,6|4,6 0,2|1,3 4,0"),
+    ('L', "0,6 0,0 4,0"),
+    ('M', "0,0 0,6 2,3 4,6 4,0"),
+    ('N', "0,0 0,6 4,0 4,6"),
+    ('O', "1,0 0,1 0,5 1,6 3,6 4,5 4,1 3,0 1,0"),
+    ('P', "0,0 0,6 3,6 4,5 4,4 3,3 0,3"),
+    ('Q', "1,0 0,1 0,5 1,6 3,6 4,5 4,1 3,0 1,0|2,2 4,0"),
+    ('R', "0,0 0,6 3,6 4,5 4,4 3,3 0,3|2,3 4,0"),
+    ('S', "4,5 3,6 1,6 0,5 0,4 1,3 3,3 4,2 4,1 3,0 1,0 0,1"),
+    ('T', "0,6 4,6|2,6 2,0"),
+    ('U', "0,6 0,1 1,0 3,0 4,1 4,6"),
+    ('V', "0,6 2,0 4,6"),
+    ('W', "0,6 1,0 2,4 3,0 4,6"),
+    ('X', "0,6 4,0|0,0 4,6"),
+    ('Y', "0,6 2,3 4,6|2,3 2,0"),
+    ('Z', "0,6 4,6 0,0 4,0"),
+    ('0', "1,0 0,1 0,5 1,6 3,6 4,5 4,1 3,0 1,0|0,1 4,5"),
+    ('1', "1,5 2,6 2,0|1,0 3,0"),
+    ('2', "0,5 1,6 3,6 4,5 4,4 0,0 4,0"),
+    ('3', "0,5 1,6 3,6 4,5 4,4 3,3 4,2 4,1 3,0 1,0 0,1|1,3 3,3"),
+    ('4', "3,0 3,6 0,2 4,2"),
+    ('5', "4,6 0,6 0,3 3,3 4,2 4,1 3,0 0,0"),
+    ('6', "4,5 3,6 1,6 0,5 0,1 1,0 3,0 4,1 4,2 3,3 0,3"),
+    ('7', "0,6 4,6 1,0"),
+    (
+        '8',
+        "1,3 0,4 0,5 1,6 3,6 4,5 4,4 3,3 1,3 0,2 0,1 1,0 3,0 4,1 4,2 3,3",
+    ),
+    ('9', "0,1 1,0 3,0 4,1 4,5 3,6 1,6 0,5 0,4 1,3 4,3"),
+    ('.', "2,0 2,0"),
+    (',', "2,0 1,-1"),
+    (':', "2,1 2,1|2,4 2,4"),
+    (';', "2,4 2,4|2,1 1,-1"),
+    ('!', "2,6 2,2|2,0 2,0"),
+    ('?', "0,5 1,6 3,6 4,5 4,4 2,3 2,2|2,0 2,0"),
+    ('-', "1,3 3,3"),
+    ('+', "0,3 4,3|2,1 2,5"),
+    ('=', "0,2 4,2|0,4 4,4"),
+    ('*', "2,1 2,5|0,2 4,4|0,4 4,2"),
+    ('/', "0,0 4,6"),
+    ('\'', "2,6 2,4"),
+    ('(', "3,6 1,4 1,2 3,0"),
+    (')', "1,6 3,4 3,2 1,0"),
+    ('_', "0,-1 4,-1"),
+];
+
+/// Polylines of the glyph of `c`, lower case letters use the upper case glyphs
+fn glyph(c: char) -> Option<&'static str> {
+    let c = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|(g, _)| *g == c)
+        .map(|(_, strokes)| *strokes)
+}
+
+/// Lay out `text` in a single line and return the strokes as segments `[ax, ay, bx, by]`.
+/// The text is centered at the origin and capital letters have the height 1.
+pub fn layout(text: &str) -> Result<Vec<[f32; 4]>, String> {
+    let advance = GLYPH_WIDTH + GLYPH_SPACING;
+    let width = (text.chars().count() as f32 * advance - GLYPH_SPACING).max(0.0);
+    let origin = (-0.5 * width, -0.5 * GLYPH_HEIGHT);
+
+    let mut segments = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        let strokes = glyph(c).ok_or_else(|| format!("The font has no glyph for `{c}`"))?;
+        let x = origin.0 + i as f32 * advance;
+        for polyline in strokes.split('|').filter(|p| !p.is_empty()) {
+            let points: Vec<(f32, f32)> = polyline
+                .split(' ')
+                .map(|point| {
+                    let (px, py) = point.split_once(',').expect("Invalid glyph point");
+                    let coordinate = |v: &str| v.parse::<f32>().expect("Invalid glyph coordinate");
+                    (
+                        (x + coordinate(px)) / GLYPH_HEIGHT,
+                        (origin.1 + coordinate(py)) / GLYPH_HEIGHT,
+                    )
+                })
+                .collect();
+            segments.extend(points.windows(2).map(|s| [s[0].0, s[0].1, s[1].0, s[1].1]));
+        }
+    }
+    Ok(segments)
+}
+
+/// A text constant
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text {
+    /// Name of the constant, e.g. `LABEL`
+    pub name: String,
+    /// Text in the source file
+    pub default: String,
+    /// Text used in the shader, either the default or an override
+    pub value: String,
+    /// Description from the comment after the declaration
+    pub description: String,
+}
+
+impl Text {
+    /// Parse a line with a `@text` declaration.
+    /// Returns `None` if the line is not a `@text` declaration.
+    pub fn parse(line: &str) -> Option<Result<Self, String>> {
+        let declaration = line.trim().strip_prefix("@text")?;
+        Some(Self::parse_declaration(declaration))
+    }
+
+    fn parse_declaration(declaration: &str) -> Result<Self, String> {
+        let declaration = declaration
+            .trim_start()
+            .strip_prefix("const ")
+            .ok_or("Expected `const` after @text")?;
+        let (name, declaration) = declaration
+            .split_once('=')
+            .ok_or("Text needs a default value")?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("Invalid text name `{name}`"));
+        }
+
+        let (default, description) = declaration
+            .trim_start()
+            .strip_prefix('"')
+            .and_then(|rest| rest.split_once('"'))
+            .ok_or("Expected a text in double quotes")?;
+        let description = description
+            .trim_start()
+            .strip_prefix(';')
+            .ok_or("Missing `;` after text")?
+            .trim()
+            .trim_start_matches('/')
+            .trim()
+            .to_string();
+
+        layout(default)?;
+        Ok(Self {
+            name: name.to_string(),
+            default: default.to_string(),
+            value: default.to_string(),
+            description,
+        })
+    }
+
+    /// Override the text, e.g. from the command line. Surrounding double quotes are removed.
+    pub fn set_value(&mut self, value: &str) -> Result<(), String> {
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        layout(value)?;
+        self.value = value.to_string();
+        Ok(())
+    }
+
+    /// WGSL declaration of the handle to the `count` strokes starting at `start`
+    pub fn declaration(&self, start: usize, count: usize) -> String {
+        format!("const {}: vec2u = vec2u({start}u, {count}u);", self.name)
+    }
+}
+
+/// Displays the text like `LABEL: text = "SDF2MESH" Label on the front`
+impl std::fmt::Display for Text {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: text = \"{}\"", self.name, self.value)?;
+        if self.value != self.default {
+            write!(f, " (default \"{}\")", self.default)?;
+        }
+        if !self.description.is_empty() {
+            write!(f, " {}", self.description)?;
+        }
+        Ok(())
+    }
+}
+
+/// Name of the generated array with the strokes of all texts
+pub const SEGMENTS_NAME: &str = "SDF3D_TEXT_SEGMENTS";
+
+/// WGSL declaration of the array with the strokes of all texts.
+/// The array has at least one element because WGSL does not allow empty arrays.
+pub fn text_segments_wgsl(segments: &[[f32; 4]]) -> String {
+    let elements: Vec<String> = if segments.is_empty() {
+        vec!["vec4f()".to_string()]
+    } else {
+        segments
+            .iter()
+            .map(|[ax, ay, bx, by]| format!("vec4({ax:?}, {ay:?}, {bx:?}, {by:?})"))
+            .collect()
+    };
+    format!(
+        "var<private> {SEGMENTS_NAME}: array<vec4f, {}> = array(\n    {}\n);\n",
+        elements.len(),
+        elements.join(",\n    ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Text::parse("const LABEL = 1;"), None);
+
+        let mut text = Text::parse("@text const LABEL = \"Hi; 2\"; // Label")
+            .unwrap()
+            .unwrap();
+        assert_eq!(text.name, "LABEL");
+        assert_eq!(text.value, "Hi; 2");
+        assert_eq!(text.description, "Label");
+        assert_eq!(
+            text.declaration(3, 10),
+            "const LABEL: vec2u = vec2u(3u, 10u);"
+        );
+
+        text.set_value("\"OK\"").unwrap();
+        assert_eq!(text.value, "OK");
+        assert_eq!(
+            text.to_string(),
+            "LABEL: text = \"OK\" (default \"Hi; 2\") Label"
+        );
+        assert!(text.set_value("€").is_err());
+
+        assert!(Text::parse("@text const LABEL = HI;").unwrap().is_err());
+        assert!(Text::parse("@text const LABEL = \"HI\"").unwrap().is_err());
+    }
+
+    #[test]
+    fn layout_is_centered() {
+        let segments = layout("HH").unwrap();
+        // H has 3 strokes
+        assert_eq!(segments.len(), 6);
+
+        let (min, max) = segments
+            .iter()
+            .flat_map(|s| [s[0], s[2]])
+            .fold((f32::MAX, f32::MIN), |(min, max), x| {
+                (min.min(x), max.max(x))
+            });
+        assert!((min + max).abs() < 1e-6);
+        assert!((max - min - 10.0 / 6.0).abs() < 1e-6);
+
+        let (min, max) = segments
+            .iter()
+            .flat_map(|s| [s[1], s[3]])
+            .fold((f32::MAX, f32::MIN), |(min, max), y| {
+                (min.min(y), max.max(y))
+            });
+        assert_eq!((min, max), (-0.5, 0.5));
+
+        assert_eq!(layout("ab").unwrap(), layout("AB").unwrap());
+        assert!(layout("").unwrap().is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 // Copyright © Michael Winkelmann <michael@winkelmann.site>
 // SPDX-License-Identifier: AGPL-3.0-or-later
-use crate::png;
+use crate::{channel, png};
 
 pub struct Rgba32FloatTextureStorage {
     data: Vec<f32>,
@@ -70,7 +70,7 @@ impl Rgba32FloatTextureStorage {
         }
     }
 
-    pub fn bind_group_entry(&self) -> wgpu::BindGroupEntry {
+    pub fn bind_group_entry(&self) -> wgpu::BindGroupEntry<'_> {
         wgpu::BindGroupEntry {
             binding: self.binding_id,
             resource: wgpu::BindingResource::TextureView(&self.view),
@@ -127,3 +127,118 @@ impl png::ToPngFile for Rgba32FloatTextureStorage {
         png::image_data_to_file(image_data, self.dims, path);
     }
 }
+
+/// Texture and sampler of a channel, bound in the group `channel::CHANNEL_GROUP`
+pub struct ChannelTexture {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    view_dimension: wgpu::TextureViewDimension,
+    index: u32,
+}
+
+impl ChannelTexture {
+    /// Upload the image of channel `index`
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        channel: &channel::Channel,
+        index: usize,
+    ) -> Self {
+        let [width, height, depth] = channel.image.size;
+        let (dimension, view_dimension) = match channel.image.volume {
+            true => (wgpu::TextureDimension::D3, wgpu::TextureViewDimension::D3),
+            false => (wgpu::TextureDimension::D2, wgpu::TextureViewDimension::D2),
+        };
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: depth,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension,
+            format: match channel.srgb {
+                true => wgpu::TextureFormat::Rgba8UnormSrgb,
+                false => wgpu::TextureFormat::Rgba8Unorm,
+            },
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &channel.image.data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        let address_mode = match channel.wrap {
+            channel::Wrap::Clamp => wgpu::AddressMode::ClampToEdge,
+            channel::Wrap::Repeat => wgpu::AddressMode::Repeat,
+        };
+        let filter = match channel.filter {
+            channel::Filter::Nearest => wgpu::FilterMode::Nearest,
+            channel::Filter::Linear => wgpu::FilterMode::Linear,
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler,
+            view_dimension,
+            index: index as u32,
+        }
+    }
+
+    pub fn bind_group_layout_entries(&self) -> [wgpu::BindGroupLayoutEntry; 2] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: 2 * self.index,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: self.view_dimension,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2 * self.index + 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ]
+    }
+
+    pub fn bind_group_entries(&self) -> [wgpu::BindGroupEntry<'_>; 2] {
+        [
+            wgpu::BindGroupEntry {
+                binding: 2 * self.index,
+                resource: wgpu::BindingResource::TextureView(&self.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2 * self.index + 1,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ]
+    }
+}